@@ -0,0 +1,82 @@
+// Counts the allocations of bulk loads into collections which are sized up front and ones which
+// grow as they go
+// Run with `cargo bench --bench alloc`
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use mktemp::Temp;
+use servicefile::{parse_file_with_options, parse_reader, ParseOptions, ServiceDatabase};
+
+const SERVICES: usize = 10_000;
+
+// Counts calls to `alloc` and `realloc`
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let result = f();
+    let count = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    drop(result);
+    count
+}
+
+fn main() {
+    let contents: String = (0..SERVICES)
+        .map(|i| {
+            format!(
+                "service-{:<10} {}/tcp # service number {}\n",
+                i,
+                1024 + i,
+                i
+            )
+        })
+        .collect();
+    let temp_file = Temp::new_file().unwrap();
+    fs::write(temp_file.as_path(), &contents).unwrap();
+    let opts = ParseOptions::default();
+
+    // the file's size gives an estimate of its number of entries, a reader's doesn't
+    let sized = allocations(|| parse_file_with_options(temp_file.as_path(), &opts).unwrap());
+    let grown = allocations(|| {
+        let reader = BufReader::new(File::open(temp_file.as_path()).unwrap());
+        parse_reader(reader, &opts).unwrap()
+    });
+    println!("parse_file:    {:>8} allocations", sized);
+    println!("parse_reader:  {:>8} allocations", grown);
+
+    let entries = parse_reader(contents.as_bytes(), &opts).unwrap();
+    let insert = |mut db: ServiceDatabase| {
+        for entry in &entries {
+            db.entry(entry.name.clone(), entry.protocol.clone())
+                .or_insert_with(|| entry.clone());
+        }
+        db
+    };
+    let sized = allocations(|| insert(ServiceDatabase::with_capacity(SERVICES)));
+    let grown = allocations(|| insert(ServiceDatabase::default()));
+    println!("with_capacity: {:>8} allocations", sized);
+    println!("default:       {:>8} allocations", grown);
+}
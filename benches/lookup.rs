@@ -0,0 +1,55 @@
+// Compares name lookups with and without ParseOptions::build_case_folded_index, and parsing lines
+// with and without their aliases
+// Run with `cargo bench`
+use std::time::Instant;
+
+use servicefile::{parse_line_no_aliases, ParseOptions, ServiceDatabase, ServiceEntry};
+
+const SERVICES: usize = 10_000;
+const LOOKUPS: usize = 10_000;
+
+fn time_lookups(db: &ServiceDatabase) -> f64 {
+    let start = Instant::now();
+    for i in 0..LOOKUPS {
+        let name = format!("SERVICE-{}", (i * 7919) % SERVICES);
+        assert!(db.get_by_name(&name, Some("tcp")).is_some());
+    }
+    start.elapsed().as_secs_f64() * 1e9 / LOOKUPS as f64
+}
+
+fn time_parse<F: Fn(&str) -> ServiceEntry>(lines: &[String], parse: F) -> f64 {
+    let start = Instant::now();
+    for line in lines {
+        assert_eq!(parse(line).protocol, "tcp");
+    }
+    start.elapsed().as_secs_f64() * 1e9 / lines.len() as f64
+}
+
+fn main() {
+    let entries: Vec<ServiceEntry> = (0..SERVICES)
+        .map(|i| {
+            ServiceEntry::builder(format!("service-{}", i), 1024 + i as u16, "tcp")
+                .build()
+                .unwrap()
+        })
+        .collect();
+
+    let indexed = ServiceDatabase::new(entries.clone());
+    let opts = ParseOptions {
+        build_case_folded_index: false,
+        ..Default::default()
+    };
+    let scanned = ServiceDatabase::with_options(entries, &opts);
+
+    println!("indexed: {:>12.0} ns/lookup", time_lookups(&indexed));
+    println!("scanned: {:>12.0} ns/lookup", time_lookups(&scanned));
+
+    let aliases: Vec<_> = (0..20).map(|i| format!("alias-{}", i)).collect();
+    let lines: Vec<_> = (0..SERVICES)
+        .map(|i| format!("service-{} {}/tcp {}", i, 1024 + i, aliases.join(" ")))
+        .collect();
+    let with_aliases = time_parse(&lines, |line| line.parse().unwrap());
+    let without_aliases = time_parse(&lines, |line| parse_line_no_aliases(line).unwrap());
+    println!("with aliases:    {:>8.0} ns/line", with_aliases);
+    println!("without aliases: {:>8.0} ns/line", without_aliases);
+}
@@ -0,0 +1,141 @@
+#[cfg(not(feature = "std"))]
+use alloc::string::{String, ToString};
+
+use crate::{ParseError, ServiceEntry};
+
+/// Incrementally constructs a `ServiceEntry`, see `ServiceEntry::builder`
+#[derive(Debug, Clone)]
+pub struct ServiceEntryBuilder {
+    entry: ServiceEntry,
+}
+
+impl ServiceEntry {
+    /// Start building an entry for `name` on `port`/`protocol` with no aliases
+    pub fn builder(
+        name: impl Into<String>,
+        port: u16,
+        protocol: impl Into<String>,
+    ) -> ServiceEntryBuilder {
+        ServiceEntryBuilder {
+            entry: ServiceEntry {
+                name: name.into(),
+                port,
+                protocol: protocol.into(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+// A field is only valid if it would be read back as the same token from a services file
+fn validate_field(field: &'static str, value: &str) -> Result<(), ParseError> {
+    if value.is_empty() || value.starts_with('#') || value.chars().any(char::is_whitespace) {
+        return Err(ParseError::InvalidField {
+            field,
+            value: value.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+impl ServiceEntryBuilder {
+    /// Add an alias to the entry
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        self.entry.aliases.push(alias.into());
+        self
+    }
+
+    /// Finish building the entry. Fails if any field couldn't be written to and parsed back from
+    /// a services file, e.g. if it's empty, contains whitespace or starts a comment.
+    pub fn build(self) -> Result<ServiceEntry, ParseError> {
+        validate_field("name", &self.entry.name)?;
+        validate_field("protocol", &self.entry.protocol)?;
+        if self.entry.protocol.contains('/') {
+            return Err(ParseError::InvalidField {
+                field: "protocol",
+                value: self.entry.protocol,
+            });
+        }
+        for alias in &self.entry.aliases {
+            validate_field("alias", alias)?;
+            // A `#` anywhere in an alias starts a comment unless
+            // `ParseOptions::literal_hash_in_aliases` is set
+            if alias.contains('#') {
+                return Err(ParseError::InvalidField {
+                    field: "alias",
+                    value: alias.clone(),
+                });
+            }
+        }
+
+        Ok(self.entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_with_aliases() {
+        assert_eq!(
+            ServiceEntry::builder("http", 80, "tcp")
+                .alias("www")
+                .alias("www-http")
+                .build(),
+            Ok(ServiceEntry {
+                name: "http".to_string(),
+                port: 80,
+                protocol: "tcp".to_string(),
+                aliases: vec!("www".to_string(), "www-http".to_string()),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn build_invalid() {
+        assert_eq!(
+            ServiceEntry::builder("", 80, "tcp").build(),
+            Err(ParseError::InvalidField {
+                field: "name",
+                value: "".to_string()
+            })
+        );
+        assert_eq!(
+            ServiceEntry::builder("http", 80, "tcp/udp").build(),
+            Err(ParseError::InvalidField {
+                field: "protocol",
+                value: "tcp/udp".to_string()
+            })
+        );
+        assert_eq!(
+            ServiceEntry::builder("http", 80, "tcp")
+                .alias("world wide web")
+                .build(),
+            Err(ParseError::InvalidField {
+                field: "alias",
+                value: "world wide web".to_string()
+            })
+        );
+        assert_eq!(
+            ServiceEntry::builder("http", 80, "tcp")
+                .alias("#www")
+                .build(),
+            Err(ParseError::InvalidField {
+                field: "alias",
+                value: "#www".to_string()
+            })
+        );
+        assert_eq!(
+            ServiceEntry::builder("http", 80, "tcp")
+                .alias("www#1")
+                .build(),
+            Err(ParseError::InvalidField {
+                field: "alias",
+                value: "www#1".to_string()
+            })
+        );
+    }
+}
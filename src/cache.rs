@@ -0,0 +1,281 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{LookupPolicy, ParseError, ParseOptions, Parser, ServiceDatabase};
+
+// Bump whenever the serialized layout of the cache, ServiceDatabase or ServiceEntry changes
+const CACHE_VERSION: u32 = 10;
+
+// What a cache written by `load_cache_or_parse` was parsed from, so that it's only reused for the
+// same options and while none of the files have changed
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct CacheSource {
+    // `options_key` of the options the entries were parsed with
+    options: String,
+    // the files included by the parsed file, see `ParseOptions::follow_includes`
+    includes: Vec<PathBuf>,
+}
+
+// The options which change the parsed entries, formatted so that they can be compared with those
+// of a cache. Options which only affect the database or progress reporting are left out, since
+// `load_cache_or_parse` applies them to cached entries as well.
+fn options_key(opts: &ParseOptions) -> String {
+    let opts = ParseOptions {
+        progress_interval: 0,
+        build_case_folded_index: false,
+        lookup_policy: LookupPolicy::default(),
+        ..opts.clone()
+    };
+    format!("{:?}", opts)
+}
+
+fn cache_error(err: bincode::ErrorKind) -> ParseError {
+    match err {
+        bincode::ErrorKind::Io(err) => ParseError::Io(err),
+        err => ParseError::InvalidCache(err.to_string()),
+    }
+}
+
+impl ServiceDatabase {
+    /// Write the database to `path` in a compact binary format which can be read back much faster
+    /// than parsing a services file
+    /// Only the entries and lookup options are written, the indexes are rebuilt when loading.
+    pub fn save_cache(&self, path: &Path) -> Result<(), ParseError> {
+        write_cache(path, None, self)
+    }
+
+    /// Read a database written by `save_cache`
+    /// Fails with `CacheVersionMismatch` if the cache was written by an incompatible version of
+    /// this crate
+    pub fn load_cache(path: &Path) -> Result<ServiceDatabase, ParseError> {
+        let (_, mut db) = read_cache(path)?;
+        db.rebuild_indexes();
+        Ok(db)
+    }
+
+    /// Load the cache at `cache_path` if it was written by this function with the same parse
+    /// options, and is newer than `path` and every file it includes. Otherwise parse `path` and
+    /// rewrite the cache.
+    /// Either way the database is built with `opts` like `with_options`, so the lookup options
+    /// don't need to match those the cache was written with.
+    pub fn load_cache_or_parse(
+        cache_path: &Path,
+        path: &Path,
+        opts: &ParseOptions,
+    ) -> Result<ServiceDatabase, ParseError> {
+        let options = options_key(opts);
+        if let Ok(cache_time) = modified(cache_path) {
+            if let Ok((Some(source), mut db)) = read_cache(cache_path) {
+                let fresh = source.options == options
+                    && std::iter::once(path)
+                        .chain(source.includes.iter().map(PathBuf::as_path))
+                        .all(|file| modified(file).is_ok_and(|time| time <= cache_time));
+                if fresh {
+                    let entries = std::mem::take(db.entries_mut());
+                    return Ok(ServiceDatabase::with_options(entries, opts));
+                }
+            }
+        }
+
+        let mut parser = Parser::new(opts, |_| {});
+        parser.parse_file(path)?;
+        let mut includes = std::mem::take(&mut parser.files);
+        // the first file is `path` itself
+        if !includes.is_empty() {
+            includes.remove(0);
+        }
+        let db = ServiceDatabase::with_options(parser.finish().0, opts);
+        let source = CacheSource { options, includes };
+        write_cache(cache_path, Some(&source), &db)?;
+        Ok(db)
+    }
+}
+
+fn modified(path: &Path) -> Result<SystemTime, ParseError> {
+    Ok(fs::metadata(path)?.modified()?)
+}
+
+fn write_cache(
+    path: &Path,
+    source: Option<&CacheSource>,
+    db: &ServiceDatabase,
+) -> Result<(), ParseError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    bincode::serialize_into(&mut writer, &(CACHE_VERSION, source, db))
+        .map_err(|err| cache_error(*err))?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Reads a database written by `write_cache`, without building its indexes
+fn read_cache(path: &Path) -> Result<(Option<CacheSource>, ServiceDatabase), ParseError> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let version: u32 = bincode::deserialize_from(&mut reader).map_err(|err| cache_error(*err))?;
+    if version != CACHE_VERSION {
+        return Err(ParseError::CacheVersionMismatch {
+            found: version,
+            expected: CACHE_VERSION,
+        });
+    }
+
+    let source = bincode::deserialize_from(&mut reader).map_err(|err| cache_error(*err))?;
+    let db = bincode::deserialize_from(&mut reader).map_err(|err| cache_error(*err))?;
+    Ok((source, db))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate mktemp;
+    use mktemp::Temp;
+
+    use super::*;
+
+    const SERVICES: &str = "\
+        tcpmux 1/tcp\n\
+        http 80/tcp www www-http\n\
+        http 80/udp www www-http\n\
+    ";
+
+    #[test]
+    fn round_trip() {
+        let db = ServiceDatabase::new(
+            crate::parse_reader(SERVICES.as_bytes(), &ParseOptions::default()).unwrap(),
+        );
+        let temp_file = Temp::new_file().unwrap();
+
+        db.save_cache(temp_file.as_path()).unwrap();
+        let loaded = ServiceDatabase::load_cache(temp_file.as_path()).unwrap();
+        assert_eq!(loaded.entries(), db.entries());
+        assert_eq!(
+            loaded.get_by_alias("www", Some("udp")),
+            Some(&db.entries()[2])
+        );
+        assert_eq!(loaded.get_by_port(1, None), Some(&db.entries()[0]));
+    }
+
+    #[test]
+    fn version_mismatch() {
+        let temp_file = Temp::new_file().unwrap();
+        let file = File::create(temp_file.as_path()).unwrap();
+        bincode::serialize_into(file, &(CACHE_VERSION + 1, ServiceDatabase::default())).unwrap();
+
+        assert_eq!(
+            ServiceDatabase::load_cache(temp_file.as_path()).unwrap_err(),
+            ParseError::CacheVersionMismatch {
+                found: CACHE_VERSION + 1,
+                expected: CACHE_VERSION
+            }
+        );
+    }
+
+    #[test]
+    fn indexes_rebuilt() {
+        let entries = crate::parse_reader(SERVICES.as_bytes(), &ParseOptions::default()).unwrap();
+        let mut db = ServiceDatabase::new(entries);
+        let temp_file = Temp::new_file().unwrap();
+
+        // indexes which don't match the entries aren't cached
+        db.entries_mut().truncate(1);
+        db.save_cache(temp_file.as_path()).unwrap();
+        let loaded = ServiceDatabase::load_cache(temp_file.as_path()).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.get_by_alias("www", None), None);
+        assert_eq!(loaded.get_by_port(80, None), None);
+        assert_eq!(
+            loaded.get_by_name("tcpmux", None),
+            Some(&loaded.entries()[0])
+        );
+    }
+
+    #[test]
+    fn cache_uses_options() {
+        let temp_dir = Temp::new_dir().unwrap();
+        let services = temp_dir.as_path().join("services");
+        let cache = temp_dir.as_path().join("services.cache");
+        fs::write(&services, SERVICES).unwrap();
+
+        let opts = ParseOptions {
+            lookup_policy: crate::LookupPolicy::LastWins,
+            ..Default::default()
+        };
+        // parsed, then loaded from the cache written by the first call
+        for _ in 0..2 {
+            let db = ServiceDatabase::load_cache_or_parse(&cache, &services, &opts).unwrap();
+            assert_eq!(db.get_by_name("http", None).unwrap().protocol, "udp");
+        }
+        let db = ServiceDatabase::load_cache_or_parse(&cache, &services, &ParseOptions::default())
+            .unwrap();
+        assert_eq!(db.get_by_name("http", None).unwrap().protocol, "tcp");
+    }
+
+    #[test]
+    fn cache_checks_parse_options() {
+        let temp_dir = Temp::new_dir().unwrap();
+        let services = temp_dir.as_path().join("services");
+        let cache = temp_dir.as_path().join("services.cache");
+        fs::write(&services, "http 80/tcp # web\n").unwrap();
+
+        let db = ServiceDatabase::load_cache_or_parse(&cache, &services, &ParseOptions::default())
+            .unwrap();
+        assert_eq!(db.entries()[0].comment, None);
+
+        // the cache was parsed without comments, so it isn't reused
+        let opts = ParseOptions {
+            keep_comments: true,
+            ..Default::default()
+        };
+        let db = ServiceDatabase::load_cache_or_parse(&cache, &services, &opts).unwrap();
+        assert_eq!(db.entries()[0].comment.as_deref(), Some("web"));
+        let cached = ServiceDatabase::load_cache(&cache).unwrap();
+        assert_eq!(cached.entries()[0].comment.as_deref(), Some("web"));
+    }
+
+    #[test]
+    fn cache_checks_includes() {
+        let temp_dir = Temp::new_dir().unwrap();
+        let services = temp_dir.as_path().join("services");
+        let custom = temp_dir.as_path().join("custom");
+        let cache = temp_dir.as_path().join("services.cache");
+        fs::write(&services, "tcpmux 1/tcp\ninclude custom\n").unwrap();
+        fs::write(&custom, "myservice 9000/tcp\n").unwrap();
+
+        let opts = ParseOptions {
+            follow_includes: true,
+            ..Default::default()
+        };
+        let db = ServiceDatabase::load_cache_or_parse(&cache, &services, &opts).unwrap();
+        assert_eq!(db.len(), 2);
+
+        // only the included file changed, after the cache was written
+        fs::write(&custom, "myservice 9000/tcp\nother 9001/tcp\n").unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(&custom)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let db = ServiceDatabase::load_cache_or_parse(&cache, &services, &opts).unwrap();
+        assert_eq!(db.len(), 3);
+    }
+
+    #[test]
+    fn fall_back_to_parse() {
+        let temp_dir = Temp::new_dir().unwrap();
+        let services = temp_dir.as_path().join("services");
+        let cache = temp_dir.as_path().join("services.cache");
+        fs::write(&services, SERVICES).unwrap();
+        fs::write(&cache, b"not a cache").unwrap();
+
+        let opts = ParseOptions::default();
+        let db = ServiceDatabase::load_cache_or_parse(&cache, &services, &opts).unwrap();
+        assert_eq!(db.len(), 3);
+
+        // the invalid cache was replaced with the parsed database
+        fs::remove_file(&services).unwrap();
+        assert_eq!(ServiceDatabase::load_cache(&cache).unwrap().len(), 3);
+    }
+}
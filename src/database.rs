@@ -0,0 +1,1862 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use std::io::BufRead;
+use std::iter::FromIterator;
+use std::ops::RangeInclusive;
+
+use crate::writer::escape_unwritable;
+use crate::{
+    parse_reader, LookupPolicy, ParseError, ParseOptions, PortClass, ServiceEntry,
+    ServiceFileWriter,
+};
+
+/// A collection of parsed entries, indexed for lookups by name, alias and port
+/// All lookups are case-insensitive
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServiceDatabase {
+    entries: Vec<ServiceEntry>,
+    // The indexes aren't cached, they're rebuilt when loading a cache so that a corrupt cache
+    // can't hold indices past the end of `entries`
+
+    // lowercased name/alias -> indices into entries, in file order
+    #[cfg_attr(feature = "cache", serde(skip))]
+    names: HashMap<String, Vec<usize>>,
+    #[cfg_attr(feature = "cache", serde(skip))]
+    aliases: HashMap<String, Vec<usize>>,
+    #[cfg_attr(feature = "cache", serde(skip))]
+    ports: HashMap<u16, Vec<usize>>,
+    // entries covering a range of ports, which are not in `ports`
+    #[cfg_attr(feature = "cache", serde(skip))]
+    ranges: Vec<usize>,
+    // `names` and `aliases` are left empty and lookups scan `entries` instead, see
+    // `ParseOptions::build_case_folded_index`
+    unindexed_names: bool,
+    // which entry lookups return when several match, see `ParseOptions::lookup_policy`
+    lookup_policy: LookupPolicy,
+}
+
+type NameIndex = HashMap<String, Vec<usize>>;
+
+// Roughly the number of entries of /etc/services per distinct alias, since most services have
+// none and TCP and UDP entries share theirs, used to size the alias index like `reserve_hint`
+// sizes the entries
+const ENTRIES_PER_ALIAS: usize = 4;
+
+// Builds the lowercased name and alias indexes
+fn fold_names(entries: &[ServiceEntry]) -> (NameIndex, NameIndex) {
+    let mut names: NameIndex = HashMap::new();
+    let mut aliases: NameIndex = HashMap::new();
+    for (idx, entry) in entries.iter().enumerate() {
+        names
+            .entry(entry.name.to_lowercase())
+            .or_default()
+            .push(idx);
+        for alias in &entry.aliases {
+            aliases.entry(alias.to_lowercase()).or_default().push(idx);
+        }
+    }
+
+    (names, aliases)
+}
+
+fn matches_protocol(entry: &ServiceEntry, protocol: Option<&str>) -> bool {
+    match protocol {
+        Some(protocol) => entry.protocol.eq_ignore_ascii_case(protocol),
+        None => true,
+    }
+}
+
+// Inserts `idx` into indices in ascending order, which is at the end when appending an entry
+fn insert_index(indices: &mut Vec<usize>, idx: usize) {
+    let pos = indices.partition_point(|&i| i <= idx);
+    indices.insert(pos, idx);
+}
+
+// Removes `idx` from the indices under `key`, along with the key if no others are left
+fn remove_index<K: Hash + Eq>(index: &mut HashMap<K, Vec<usize>>, key: &K, idx: usize) {
+    if let Some(indices) = index.get_mut(key) {
+        indices.retain(|&i| i != idx);
+        if indices.is_empty() {
+            index.remove(key);
+        }
+    }
+}
+
+// See `ServiceDatabase::to_services_file`
+fn services_file<'a>(
+    entries: impl IntoIterator<Item = &'a ServiceEntry>,
+    include_header: bool,
+) -> String {
+    let mut writer = ServiceFileWriter::new(Vec::new());
+    // Writing to a Vec can't fail, and escaped entries are never rejected
+    if include_header {
+        writer
+            .write_comment("/etc/services:\n\nNetwork services, Internet style\n")
+            .unwrap();
+    }
+    for entry in entries {
+        writer.write_entry(&escape_unwritable(entry)).unwrap();
+    }
+
+    String::from_utf8(writer.into_inner()).unwrap()
+}
+
+impl ServiceDatabase {
+    /// A reasonable limit for `find_overlong_names`, longer names exceed typical NSS limits
+    pub const DEFAULT_MAX_NAME_LEN: usize = 63;
+
+    /// Build a database from `entries`, keeping them in the given order
+    pub fn new(entries: Vec<ServiceEntry>) -> Self {
+        Self::with_options(entries, &ParseOptions::default())
+    }
+
+    /// Build a database like `new`, only indexing names and aliases if
+    /// `opts.build_case_folded_index` is set, and resolving lookups which match several entries
+    /// according to `opts.lookup_policy`
+    pub fn with_options(entries: Vec<ServiceEntry>, opts: &ParseOptions) -> Self {
+        let mut db = ServiceDatabase {
+            entries,
+            names: HashMap::new(),
+            aliases: HashMap::new(),
+            ports: HashMap::new(),
+            ranges: Vec::new(),
+            unindexed_names: !opts.build_case_folded_index,
+            lookup_policy: opts.lookup_policy,
+        };
+        db.rebuild_indexes();
+        db
+    }
+
+    /// Parse a reader like `parse_reader` and build a database of its entries like
+    /// `with_options`, e.g. for a socket or decompressor rather than a path
+    /// The parsed entries are moved into the database rather than copied.
+    pub fn from_reader<R: BufRead>(reader: R, opts: &ParseOptions) -> Result<Self, ParseError> {
+        Ok(Self::with_options(parse_reader(reader, opts)?, opts))
+    }
+
+    /// An empty database with room for `capacity` entries before reallocating, e.g. before adding
+    /// many entries with `entry`
+    pub fn with_capacity(capacity: usize) -> Self {
+        ServiceDatabase {
+            entries: Vec::with_capacity(capacity),
+            names: HashMap::with_capacity(capacity),
+            aliases: HashMap::with_capacity(capacity / ENTRIES_PER_ALIAS),
+            ports: HashMap::with_capacity(capacity),
+            ..Default::default()
+        }
+    }
+
+    /// Build a database from `base` where each of `overrides` replaces the base entries with the
+    /// same name and protocol, e.g. to start from /etc/services but move a service in tests
+    /// Overrides come first so lookups find them before any remaining base entries.
+    pub fn with_overrides(base: ServiceDatabase, overrides: Vec<ServiceEntry>) -> Self {
+        let shadowed: HashSet<_> = overrides
+            .iter()
+            .map(|entry| (entry.name.to_lowercase(), entry.protocol.to_lowercase()))
+            .collect();
+
+        let mut db = base;
+        db.entries.retain(|entry| {
+            !shadowed.contains(&(entry.name.to_lowercase(), entry.protocol.to_lowercase()))
+        });
+        db.entries.splice(0..0, overrides);
+        db.rebuild_indexes();
+        db
+    }
+
+    /// Build a database from `(name, port)` pairs, e.g. for a small in-memory registry
+    /// Every entry gets the protocol `tcp` and no aliases.
+    pub fn from_name_port_pairs<I: IntoIterator<Item = (String, u16)>>(pairs: I) -> Self {
+        let entries = pairs
+            .into_iter()
+            .map(|(name, port)| ServiceEntry {
+                name,
+                port,
+                protocol: "tcp".to_string(),
+                ..Default::default()
+            })
+            .collect();
+        ServiceDatabase::new(entries)
+    }
+
+    /// Rebuild the name, alias and port indexes from the entries, which must be done after
+    /// changing them through `entries_mut`, otherwise lookups may miss entries or return the
+    /// wrong ones
+    pub fn rebuild_indexes(&mut self) {
+        self.names.clear();
+        self.aliases.clear();
+        self.ports.clear();
+        self.ranges.clear();
+        // most entries have their own name, and many their own port
+        self.ports.reserve(self.entries.len());
+        if !self.unindexed_names {
+            self.names.reserve(self.entries.len());
+            self.aliases.reserve(self.entries.len() / ENTRIES_PER_ALIAS);
+        }
+
+        for idx in 0..self.entries.len() {
+            self.index_entry(idx);
+        }
+    }
+
+    // Adds `entries[idx]` to the indexes, keeping the indices under each key in file order
+    fn index_entry(&mut self, idx: usize) {
+        let entry = &self.entries[idx];
+        if entry.port_range.is_some() {
+            insert_index(&mut self.ranges, idx);
+        } else {
+            insert_index(self.ports.entry(entry.port).or_default(), idx);
+        }
+
+        if self.unindexed_names {
+            return;
+        }
+        insert_index(
+            self.names.entry(entry.name.to_lowercase()).or_default(),
+            idx,
+        );
+        for alias in &entry.aliases {
+            insert_index(self.aliases.entry(alias.to_lowercase()).or_default(), idx);
+        }
+    }
+
+    // Removes `entries[idx]` from the indexes, e.g. before it's modified
+    fn unindex_entry(&mut self, idx: usize) {
+        let entry = &self.entries[idx];
+        if entry.port_range.is_some() {
+            self.ranges.retain(|&i| i != idx);
+        } else {
+            remove_index(&mut self.ports, &entry.port, idx);
+        }
+
+        if self.unindexed_names {
+            return;
+        }
+        remove_index(&mut self.names, &entry.name.to_lowercase(), idx);
+        for alias in &entry.aliases {
+            remove_index(&mut self.aliases, &alias.to_lowercase(), idx);
+        }
+    }
+
+    // Appends `entry`, keeping the indexes up to date
+    fn push(&mut self, entry: ServiceEntry) -> usize {
+        self.entries.push(entry);
+        let idx = self.entries.len() - 1;
+        self.index_entry(idx);
+        idx
+    }
+
+    // Index of the entry called `name` with `protocol` which `get_by_name` would return
+    fn position(&self, name: &str, protocol: &str) -> Option<usize> {
+        if self.unindexed_names {
+            let name = name.to_lowercase();
+            return self.pick(0..self.entries.len(), |entry| {
+                entry.name.to_lowercase() == name && matches_protocol(entry, Some(protocol))
+            });
+        }
+        self.match_idx(self.names.get(&name.to_lowercase())?, Some(protocol))
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        index: &NameIndex,
+        key: &str,
+        protocol: Option<&str>,
+    ) -> Option<&'a ServiceEntry> {
+        self.match_idx(index.get(&key.to_lowercase())?, protocol)
+            .map(|idx| &self.entries[idx])
+    }
+
+    // Linear lookup when `names` and `aliases` aren't built
+    fn scan<F: Fn(&ServiceEntry) -> bool>(
+        &self,
+        protocol: Option<&str>,
+        f: F,
+    ) -> Option<&ServiceEntry> {
+        let idx = self.pick(0..self.entries.len(), |entry| {
+            f(entry) && matches_protocol(entry, protocol)
+        })?;
+        Some(&self.entries[idx])
+    }
+
+    fn match_idx(&self, indices: &[usize], protocol: Option<&str>) -> Option<usize> {
+        self.pick(indices.iter().copied(), |entry| {
+            matches_protocol(entry, protocol)
+        })
+    }
+
+    // The first or last of `indices` whose entry satisfies `f`, depending on `lookup_policy`
+    fn pick<I, F>(&self, mut indices: I, f: F) -> Option<usize>
+    where
+        I: DoubleEndedIterator<Item = usize>,
+        F: Fn(&ServiceEntry) -> bool,
+    {
+        let matches = |idx: &usize| f(&self.entries[*idx]);
+        match self.lookup_policy {
+            LookupPolicy::FirstWins => indices.find(matches),
+            LookupPolicy::LastWins => indices.rfind(matches),
+        }
+    }
+
+    /// All entries, in the order they were added
+    pub fn entries(&self) -> &[ServiceEntry] {
+        &self.entries
+    }
+
+    /// The entries for editing in place, e.g. to sort them or change many at once
+    /// The indexes aren't updated, call `rebuild_indexes` after making changes.
+    pub fn entries_mut(&mut self) -> &mut Vec<ServiceEntry> {
+        &mut self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The first entry, in the order entries were added
+    pub fn first(&self) -> Option<&ServiceEntry> {
+        self.entries.first()
+    }
+
+    /// The last entry, in the order entries were added
+    pub fn last(&self) -> Option<&ServiceEntry> {
+        self.entries.last()
+    }
+
+    /// The number of aliases over all entries
+    pub fn total_aliases(&self) -> usize {
+        self.entries.iter().map(|entry| entry.aliases.len()).sum()
+    }
+
+    /// The number of canonical names and aliases over all entries, i.e. the size of the name and
+    /// alias indexes
+    pub fn total_names(&self) -> usize {
+        self.len() + self.total_aliases()
+    }
+
+    /// Find the entry whose canonical name is `name`
+    /// If `protocol` is given only entries with that protocol are considered. Of several matching
+    /// entries the first is returned, or the last with `LookupPolicy::LastWins`.
+    pub fn get_by_name(&self, name: &str, protocol: Option<&str>) -> Option<&ServiceEntry> {
+        if self.unindexed_names {
+            let name = name.to_lowercase();
+            return self.scan(protocol, |entry| entry.name.to_lowercase() == name);
+        }
+        self.lookup(&self.names, name, protocol)
+    }
+
+    /// All entries whose canonical name is `name`, of any protocol, in the order they were added
+    pub fn get_by_name_all(&self, name: &str) -> Vec<&ServiceEntry> {
+        let name = name.to_lowercase();
+        if self.unindexed_names {
+            return self
+                .entries
+                .iter()
+                .filter(|entry| entry.name.to_lowercase() == name)
+                .collect();
+        }
+        match self.names.get(&name) {
+            Some(indices) => indices.iter().map(|&idx| &self.entries[idx]).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// A database of only the entries for the services called `names`, with all their protocols
+    /// and aliases, e.g. to write a minimal services file for a container image
+    /// Entries are in the order of `names`, and names with no entries are skipped.
+    pub fn subset_for(&self, names: &[&str]) -> ServiceDatabase {
+        let mut seen = HashSet::new();
+        let entries = names
+            .iter()
+            .filter(|name| seen.insert(name.to_lowercase()))
+            .flat_map(|name| self.get_by_name_all(name))
+            .cloned()
+            .collect();
+
+        let mut db = ServiceDatabase {
+            entries,
+            unindexed_names: self.unindexed_names,
+            lookup_policy: self.lookup_policy,
+            ..Default::default()
+        };
+        db.rebuild_indexes();
+        db
+    }
+
+    /// Find the entry which lists `alias` as one of its aliases
+    /// If `protocol` is given only entries with that protocol are considered. Of several matching
+    /// entries the first is returned, or the last with `LookupPolicy::LastWins`.
+    pub fn get_by_alias(&self, alias: &str, protocol: Option<&str>) -> Option<&ServiceEntry> {
+        if self.unindexed_names {
+            let alias = alias.to_lowercase();
+            return self.scan(protocol, |entry| {
+                entry.aliases.iter().any(|a| a.to_lowercase() == alias)
+            });
+        }
+        self.lookup(&self.aliases, alias, protocol)
+    }
+
+    /// Find the entry on `port`, including entries with a `port_range` containing `port`
+    /// If `protocol` is given only entries with that protocol are considered. Of several matching
+    /// entries the first is returned, or the last with `LookupPolicy::LastWins`.
+    pub fn get_by_port(&self, port: u16, protocol: Option<&str>) -> Option<&ServiceEntry> {
+        let exact = self
+            .ports
+            .get(&port)
+            .and_then(|indices| self.match_idx(indices, protocol));
+        let ranged = self.pick(self.ranges.iter().copied(), |entry| {
+            let in_range = entry
+                .port_range
+                .as_ref()
+                .is_some_and(|range| range.contains(&port));
+            in_range && matches_protocol(entry, protocol)
+        });
+
+        let idx = match (exact, ranged, self.lookup_policy) {
+            (Some(a), Some(b), LookupPolicy::FirstWins) => a.min(b),
+            (Some(a), Some(b), LookupPolicy::LastWins) => a.max(b),
+            (a, b, _) => a.or(b)?,
+        };
+        Some(&self.entries[idx])
+    }
+
+    /// Find the entry on `port` like `get_by_port` without a protocol, but preferring the TCP
+    /// entry, then the UDP entry, then any other, so that e.g. port 1 gives `tcpmux 1/tcp` whatever
+    /// order the entries are in
+    pub fn get_by_port_preferred(&self, port: u16) -> Option<&ServiceEntry> {
+        self.get_by_port(port, Some("tcp"))
+            .or_else(|| self.get_by_port(port, Some("udp")))
+            .or_else(|| self.get_by_port(port, None))
+    }
+
+    /// Describe the service registered on `port`/`protocol`, e.g. `"redis (6379/tcp)"`, followed
+    /// by the entry's description if its comment was kept, e.g. `"ssh (22/tcp): Secure Shell"`
+    /// Useful to explain why binding to a port failed with "address in use"
+    pub fn describe_port(&self, port: u16, protocol: &str) -> Option<String> {
+        let entry = self.get_by_port(port, Some(protocol))?;
+        let service = format!("{} ({})", entry.name, entry.port_protocol());
+        Some(match entry.description() {
+            Some(description) => format!("{}: {}", service, description),
+            None => service,
+        })
+    }
+
+    /// Count how many entries fall in each of the system, registered and dynamic port ranges
+    /// Classes with no entries are left out
+    pub fn count_by_class(&self) -> HashMap<PortClass, usize> {
+        let mut counts = HashMap::new();
+        for entry in &self.entries {
+            *counts.entry(entry.port_class()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Count how many entries use each protocol, lowercased
+    pub fn protocol_counts(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for entry in &self.entries {
+            *counts.entry(entry.protocol.to_lowercase()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// The protocols of `protocol_counts`, most used first, e.g. to see at a glance whether a
+    /// file is mostly TCP. Protocols used by as many entries are sorted by name.
+    pub fn protocols_by_frequency(&self) -> Vec<(String, usize)> {
+        let mut counts: Vec<_> = self.protocol_counts().into_iter().collect();
+        counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+        counts
+    }
+
+    /// Find the lowest registered port (1024-49151) with no entry for `protocol`, e.g. to assign
+    /// a port to a new service. Returns `None` if every registered port is taken.
+    pub fn next_free_registered_port(&self, protocol: &str) -> Option<u16> {
+        (1024..=49151).find(|&port| self.get_by_port(port, Some(protocol)).is_none())
+    }
+
+    /// The ports in `range` with no entry for `protocol`, in order, e.g. to audit a block of ports
+    /// allocated to a family of services for ports which were missed
+    pub fn find_gaps(&self, range: RangeInclusive<u16>, protocol: &str) -> Vec<u16> {
+        range
+            .filter(|&port| self.get_by_port(port, Some(protocol)).is_none())
+            .collect()
+    }
+
+    /// Iterate over `(name, entry)` pairs for the canonical name and every alias of each entry,
+    /// i.e. the contents of the name and alias indexes
+    pub fn flatten_aliases(&self) -> impl Iterator<Item = (&str, &ServiceEntry)> {
+        self.entries.iter().flat_map(|entry| {
+            std::iter::once(&entry.name)
+                .chain(entry.aliases.iter())
+                .map(move |name| (name.as_str(), entry))
+        })
+    }
+
+    /// Find aliases which are ambiguous because they're claimed by more than one service, or
+    /// because they're the canonical name of a different service
+    /// Returns each such alias with the entries involved in file order, sorted by alias
+    pub fn find_alias_collisions(&self) -> Vec<(String, Vec<&ServiceEntry>)> {
+        let folded;
+        let (names, aliases) = if self.unindexed_names {
+            folded = fold_names(&self.entries);
+            (&folded.0, &folded.1)
+        } else {
+            (&self.names, &self.aliases)
+        };
+
+        let mut collisions = Vec::new();
+        for (alias, holders) in aliases {
+            let mut indices: BTreeSet<usize> = holders.iter().copied().collect();
+            if let Some(named) = names.get(alias) {
+                indices.extend(named);
+            }
+
+            let entries: Vec<_> = indices.into_iter().map(|idx| &self.entries[idx]).collect();
+            let services: BTreeSet<_> = entries
+                .iter()
+                .map(|entry| entry.name.to_lowercase())
+                .collect();
+            if services.len() > 1 {
+                collisions.push((alias.clone(), entries));
+            }
+        }
+
+        collisions.sort_by(|a, b| a.0.cmp(&b.0));
+        collisions
+    }
+
+    /// Find service names defined on more than one distinct port, e.g. `http` on both `80/tcp`
+    /// and `8080/tcp`, which usually means one of the entries is wrong
+    /// Returns each lowercased name with its ports in ascending order, sorted by name. A name on
+    /// the same port for several protocols is not an anomaly.
+    pub fn find_name_port_anomalies(&self) -> Vec<(String, Vec<u16>)> {
+        let mut ports: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+        for entry in &self.entries {
+            ports
+                .entry(entry.name.to_lowercase())
+                .or_default()
+                .insert(entry.port);
+        }
+
+        ports
+            .into_iter()
+            .filter(|(_, ports)| ports.len() > 1)
+            .map(|(name, ports)| (name, ports.into_iter().collect()))
+            .collect()
+    }
+
+    /// Check that every service name is on a single port, for registries with this stricter
+    /// policy, returning the lowercased names on more than one port otherwise, sorted by name
+    /// See `find_name_port_anomalies` for the ports of each name.
+    pub fn validate_unique_canonical(&self) -> Result<(), Vec<String>> {
+        let names: Vec<_> = self
+            .find_name_port_anomalies()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        if names.is_empty() {
+            Ok(())
+        } else {
+            Err(names)
+        }
+    }
+
+    /// The number of distinct services listing `alias` as an alias, ignoring case
+    /// Entries for the same service on several protocols count once, so more than 1 means the
+    /// alias is ambiguous, see `find_alias_collisions`.
+    pub fn alias_usage_count(&self, alias: &str) -> usize {
+        let alias = alias.to_lowercase();
+        let holders: Vec<&ServiceEntry> = if self.unindexed_names {
+            self.entries
+                .iter()
+                .filter(|entry| entry.aliases.iter().any(|a| a.to_lowercase() == alias))
+                .collect()
+        } else {
+            let indices = self.aliases.get(&alias).map_or(&[][..], Vec::as_slice);
+            indices.iter().map(|&idx| &self.entries[idx]).collect()
+        };
+
+        let services: HashSet<_> = holders
+            .iter()
+            .map(|entry| entry.name.to_lowercase())
+            .collect();
+        services.len()
+    }
+
+    /// Find entries whose name is longer than `max` characters, see `DEFAULT_MAX_NAME_LEN`
+    /// A very long name is usually a misparse, e.g. a comment without a leading `#`.
+    pub fn find_overlong_names(&self, max: usize) -> Vec<&ServiceEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.name.chars().count() > max)
+            .collect()
+    }
+
+    /// Entries on a port from `start` to `end` inclusive, e.g. 1024 to 49151 for the registered
+    /// ports, in the order they were added
+    /// An entry with a `port_range` is included if its range overlaps. If `start` is greater
+    /// than `end` no entries are returned.
+    pub fn entries_in_port_range(&self, start: u16, end: u16) -> Vec<&ServiceEntry> {
+        self.filter(|entry| {
+            let (first, last) = match &entry.port_range {
+                Some(range) => (*range.start(), *range.end()),
+                None => (entry.port, entry.port),
+            };
+            first <= end && start <= last
+        })
+    }
+
+    /// Entries for which `pred` returns true, in the order they were added, e.g. every UDP entry
+    pub fn filter(&self, pred: impl Fn(&ServiceEntry) -> bool) -> Vec<&ServiceEntry> {
+        self.entries.iter().filter(|entry| pred(entry)).collect()
+    }
+
+    /// Find entries whose comment contains `needle`, ignoring case, e.g. every service mentioning
+    /// "mail"
+    /// Comments are only kept when parsing with `ParseOptions::keep_comments`.
+    pub fn search_description<'a>(&'a self, needle: &str) -> Vec<&'a ServiceEntry> {
+        let needle = needle.to_lowercase();
+        self.entries
+            .iter()
+            .filter(|entry| {
+                let comment = entry.comment.as_deref().unwrap_or("");
+                comment.to_lowercase().contains(&needle)
+            })
+            .collect()
+    }
+
+    /// Every port and protocol used by an entry, e.g. to generate a firewall allow-list
+    /// Entries with a `port_range` contribute each port of the range.
+    pub fn port_protocol_pairs(&self) -> BTreeSet<(u16, String)> {
+        self.entries
+            .iter()
+            .flat_map(|entry| {
+                let ports = entry.port_range.clone().unwrap_or(entry.port..=entry.port);
+                ports.map(move |port| (port, entry.protocol.clone()))
+            })
+            .collect()
+    }
+
+    /// The canonical name on each port used with `protocol`, for callers which only need to name
+    /// ports
+    /// A port used by several entries maps to the one `get_by_port` returns, so duplicates follow
+    /// the lookup policy. Entries with a `port_range` contribute each port of the range.
+    pub fn port_name_map(&self, protocol: &str) -> HashMap<u16, String> {
+        let ports: BTreeSet<_> = self
+            .entries
+            .iter()
+            .filter(|entry| matches_protocol(entry, Some(protocol)))
+            .flat_map(|entry| entry.port_range.clone().unwrap_or(entry.port..=entry.port))
+            .collect();
+        ports
+            .into_iter()
+            .filter_map(|port| {
+                let entry = self.get_by_port(port, Some(protocol))?;
+                Some((port, entry.name.clone()))
+            })
+            .collect()
+    }
+
+    /// `(port, name, protocol)` for each entry in the order entries were added, e.g. for a table
+    /// widget
+    pub fn rows(&self) -> Vec<(u16, &str, &str)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.port, entry.name.as_str(), entry.protocol.as_str()))
+            .collect()
+    }
+
+    /// `(name, port, protocol)` for each entry like `rows`, without collecting them first
+    pub fn iter_tuples(&self) -> impl Iterator<Item = (&str, u16, &str)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.port, entry.protocol.as_str()))
+    }
+
+    /// Format the whole database as a services file, in the order of `entries`
+    /// If `include_header` is true the output starts with the usual `# /etc/services:` comment.
+    /// Every entry is written on a single line: whitespace in a name or alias is replaced by `_`
+    /// like `ServiceEntry::escaped_name`, and line breaks in a comment by spaces.
+    pub fn to_services_file(&self, include_header: bool) -> String {
+        services_file(&self.entries, include_header)
+    }
+
+    /// Format the database like `to_services_file`, with the entries sorted by port, then
+    /// protocol and name, the order of a typical /etc/services
+    pub fn to_sorted_services_file(&self, include_header: bool) -> String {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by(|a, b| (a.port, &a.protocol, &a.name).cmp(&(b.port, &b.protocol, &b.name)));
+        services_file(entries, include_header)
+    }
+
+    /// Format the database as one `name<TAB>port/protocol` line per entry, sorted by port, for
+    /// scripts which would rather use `cut` or `awk` than parse a services file
+    pub fn to_simple_table(&self) -> String {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|entry| entry.port);
+
+        entries
+            .iter()
+            .map(|entry| format!("{}\t{}\n", entry.name, entry.port_protocol()))
+            .collect()
+    }
+
+    /// Get the entry for `name`/`protocol` for conditional insertion or modification, like
+    /// `HashMap::entry`. Names and protocols are matched ignoring case.
+    pub fn entry(&mut self, name: String, protocol: String) -> DbEntry<'_> {
+        let idx = self.position(&name, &protocol);
+        DbEntry {
+            db: self,
+            name,
+            protocol,
+            idx,
+        }
+    }
+
+    /// Keep only the entries for which `f` returns true, preserving their order
+    pub fn retain<F: FnMut(&ServiceEntry) -> bool>(&mut self, f: F) {
+        self.entries.retain(f);
+        self.rebuild_indexes();
+    }
+
+    /// Release memory left over from removed entries, e.g. after `retain`, for databases which
+    /// are kept around for a long time
+    pub fn shrink(&mut self) {
+        self.entries.shrink_to_fit();
+        for index in &mut [&mut self.names, &mut self.aliases] {
+            index.shrink_to_fit();
+            index.values_mut().for_each(Vec::shrink_to_fit);
+        }
+        self.ports.shrink_to_fit();
+        self.ports.values_mut().for_each(Vec::shrink_to_fit);
+        self.ranges.shrink_to_fit();
+    }
+
+    /// Resolve a name or alias to the canonical name of the service, e.g. `www` -> `http`
+    /// Canonical names take precedence over aliases
+    pub fn canonical_name_for(&self, query: &str) -> Option<&str> {
+        self.get_by_name(query, None)
+            .or_else(|| self.get_by_alias(query, None))
+            .map(|entry| entry.name.as_str())
+    }
+
+    /// Resolve a port argument which may be a number or a service name or alias, like
+    /// `getaddrinfo`, e.g. both `80` and `http` give 80
+    /// If `protocol` is given only entries with that protocol are considered for names.
+    pub fn resolve_port_spec(&self, spec: &str, protocol: Option<&str>) -> Option<u16> {
+        if let Ok(port) = spec.parse() {
+            return Some(port);
+        }
+        self.port_of(spec, protocol)
+    }
+
+    /// The port of the service called `name`, either by its canonical name or an alias
+    /// If `protocol` is given only entries with that protocol are considered
+    pub fn port_of(&self, name: &str, protocol: Option<&str>) -> Option<u16> {
+        self.get_by_name(name, protocol)
+            .or_else(|| self.get_by_alias(name, protocol))
+            .map(|entry| entry.port)
+    }
+
+    /// Look up `query` like `port_of`, reporting how the entry was found, e.g. to debug why a
+    /// query resolves to an unexpected entry
+    pub fn explain_lookup(&self, query: &str, protocol: Option<&str>) -> LookupExplanation<'_> {
+        let (entry, matched_via) = match self.get_by_name(query, protocol) {
+            Some(entry) if entry.name == query => (Some(entry), MatchedVia::Name),
+            Some(entry) => (Some(entry), MatchedVia::CaseFolded),
+            None => match self.get_by_alias(query, protocol) {
+                Some(entry) if entry.aliases.iter().any(|alias| alias == query) => {
+                    (Some(entry), MatchedVia::Alias)
+                }
+                Some(entry) => (Some(entry), MatchedVia::CaseFolded),
+                None => (None, MatchedVia::NotFound),
+            },
+        };
+        LookupExplanation {
+            entry,
+            matched_via,
+            indexed: !self.unindexed_names,
+        }
+    }
+
+    /// The port of the service called `name` like `port_of`, or `default` if there's no such
+    /// service
+    pub fn port_or(&self, name: &str, protocol: Option<&str>, default: u16) -> u16 {
+        self.port_of(name, protocol).unwrap_or(default)
+    }
+
+    /// Find entries whose name is in `reference`, e.g. an IANA services file, but with a
+    /// different port or protocol, e.g. to catch a local `ssh 2222/tcp`
+    /// An entry is compared with the reference entry of the same name and protocol, or any
+    /// reference entry of the same name if there's none with its protocol. Names are compared by
+    /// canonical name only, ignoring case.
+    pub fn verify_against<'a>(&'a self, reference: &'a ServiceDatabase) -> Vec<PortMismatch<'a>> {
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let expected = reference
+                    .get_by_name(&entry.name, Some(&entry.protocol))
+                    .or_else(|| reference.get_by_name(&entry.name, None))?;
+                let same = expected.port == entry.port
+                    && expected.port_range == entry.port_range
+                    && matches_protocol(expected, Some(&entry.protocol));
+                if same {
+                    None
+                } else {
+                    Some(PortMismatch { entry, expected })
+                }
+            })
+            .collect()
+    }
+
+    /// Find entries on a dynamic port whose name `iana` assigns to a system port, e.g. a local
+    /// `ssh 50000/tcp`, which may be an attempt to hijack the service
+    /// Names are matched like `verify_against`.
+    pub fn find_shadowed_well_known<'a>(&'a self, iana: &ServiceDatabase) -> Vec<&'a ServiceEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.port_class() == PortClass::Dynamic)
+            .filter(|entry| {
+                iana.get_by_name(&entry.name, Some(&entry.protocol))
+                    .or_else(|| iana.get_by_name(&entry.name, None))
+                    .is_some_and(|known| known.port_class() == PortClass::System)
+            })
+            .collect()
+    }
+}
+
+/// How `ServiceDatabase::explain_lookup` found an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchedVia {
+    /// The query is the entry's canonical name
+    Name,
+    /// The query is one of the entry's aliases
+    Alias,
+    /// The query is the entry's canonical name or an alias ignoring case, but not as written
+    CaseFolded,
+    NotFound,
+}
+
+/// The result of `ServiceDatabase::explain_lookup`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupExplanation<'a> {
+    /// The entry a lookup resolves to, if any
+    pub entry: Option<&'a ServiceEntry>,
+    pub matched_via: MatchedVia,
+    /// Whether the name and alias indexes were used, rather than scanning every entry, see
+    /// `ParseOptions::build_case_folded_index`
+    pub indexed: bool,
+}
+
+/// An entry which doesn't match the entry of the same name in a reference database, see
+/// `ServiceDatabase::verify_against`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortMismatch<'a> {
+    pub entry: &'a ServiceEntry,
+    /// The entry of the reference database
+    pub expected: &'a ServiceEntry,
+}
+
+/// An entry of a `ServiceDatabase` which may or may not exist yet, see `ServiceDatabase::entry`
+pub struct DbEntry<'a> {
+    db: &'a mut ServiceDatabase,
+    name: String,
+    protocol: String,
+    // index of the existing entry, if any
+    idx: Option<usize>,
+}
+
+impl<'a> DbEntry<'a> {
+    /// The existing entry, if any
+    pub fn get(&self) -> Option<&ServiceEntry> {
+        self.idx.map(|idx| &self.db.entries[idx])
+    }
+
+    /// Insert `entry` if there's no existing entry, and return the entry in the database
+    /// The name and protocol of `entry` are replaced with the ones passed to
+    /// `ServiceDatabase::entry`.
+    pub fn or_insert(self, entry: ServiceEntry) -> &'a ServiceEntry {
+        self.or_insert_with(|| entry)
+    }
+
+    /// Like `or_insert`, only calling `f` if there's no existing entry
+    pub fn or_insert_with<F: FnOnce() -> ServiceEntry>(self, f: F) -> &'a ServiceEntry {
+        let idx = match self.idx {
+            Some(idx) => idx,
+            None => self.db.push(ServiceEntry {
+                name: self.name,
+                protocol: self.protocol,
+                ..f()
+            }),
+        };
+        &self.db.entries[idx]
+    }
+
+    /// Modify the existing entry, if any, and update the indexes
+    /// Only the modified entry is re-indexed, so this is cheap even for a large database.
+    pub fn and_modify<F: FnOnce(&mut ServiceEntry)>(self, f: F) -> Self {
+        if let Some(idx) = self.idx {
+            self.db.unindex_entry(idx);
+            f(&mut self.db.entries[idx]);
+            self.db.index_entry(idx);
+        }
+        self
+    }
+}
+
+/// Also allows collecting a `Result<ServiceDatabase, ParseError>` from `parse_reader_iter`,
+/// stopping at the first error
+impl FromIterator<ServiceEntry> for ServiceDatabase {
+    fn from_iter<I: IntoIterator<Item = ServiceEntry>>(entries: I) -> Self {
+        ServiceDatabase::new(entries.into_iter().collect())
+    }
+}
+
+/// See `ServiceDatabase::from_name_port_pairs`
+impl FromIterator<(String, u16)> for ServiceDatabase {
+    fn from_iter<I: IntoIterator<Item = (String, u16)>>(pairs: I) -> Self {
+        ServiceDatabase::from_name_port_pairs(pairs)
+    }
+}
+
+impl ServiceEntry {
+    /// Whether `iana` has an entry with this entry's name, port and protocol, e.g. to tell stock
+    /// services from ones added locally
+    /// Aliases are not compared, and an entry with a `port_range` must match a range exactly.
+    pub fn is_well_known(&self, iana: &ServiceDatabase) -> bool {
+        let candidates = iana.ports.get(&self.port).into_iter().flatten();
+        candidates.chain(&iana.ranges).any(|&idx| {
+            let entry = &iana.entries[idx];
+            entry.port == self.port
+                && entry.port_range == self.port_range
+                && entry.name.to_lowercase() == self.name.to_lowercase()
+                && matches_protocol(entry, Some(&self.protocol))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_reader_iter;
+    use std::io::Cursor;
+
+    const FIXTURE: &str = "\
+        # WELL KNOWN PORT NUMBERS\n\
+        rtmp              1/ddp    #Routing Table Maintenance Protocol\n\
+        tcpmux            1/udp     # TCP Port Service Multiplexer\n\
+        tcpmux            1/tcp     # TCP Port Service Multiplexer\n\
+        nbp               2/ddp    #Name Binding Protocol\n\
+        compressnet       2/udp     # Management Utility\n\
+        compressnet       2/tcp     # Management Utility\n\
+        compressnet       3/udp     # Compression Process\n\
+        compressnet       3/tcp     # Compression Process\n\
+    ";
+
+    fn database(contents: &str) -> ServiceDatabase {
+        ServiceDatabase::new(parse_reader(contents.as_bytes(), &ParseOptions::default()).unwrap())
+    }
+
+    #[test]
+    fn from_reader() {
+        let db =
+            ServiceDatabase::from_reader(Cursor::new(FIXTURE), &ParseOptions::default()).unwrap();
+        assert_eq!(db.len(), 8);
+        assert_eq!(db.get_by_name("nbp", None).unwrap().port, 2);
+        assert_eq!(db.get_by_port(3, Some("tcp")).unwrap().name, "compressnet");
+
+        let opts = ParseOptions {
+            lookup_policy: LookupPolicy::LastWins,
+            ..Default::default()
+        };
+        let db = ServiceDatabase::from_reader(Cursor::new(FIXTURE), &opts).unwrap();
+        assert_eq!(db.get_by_port(1, None).unwrap().protocol, "tcp");
+
+        assert_eq!(
+            ServiceDatabase::from_reader(Cursor::new("http asdf/tcp\n"), &opts).unwrap_err(),
+            ParseError::MalformedPort("asdf".to_string())
+        );
+    }
+
+    #[test]
+    fn first_and_last() {
+        let db = database(FIXTURE);
+        assert_eq!(db.first().unwrap().name, "rtmp");
+        let last = db.last().unwrap();
+        assert_eq!(
+            (last.name.as_str(), last.port, last.protocol.as_str()),
+            ("compressnet", 3, "tcp")
+        );
+
+        assert_eq!(ServiceDatabase::default().first(), None);
+        assert_eq!(ServiceDatabase::default().last(), None);
+    }
+
+    #[test]
+    fn name_counts() {
+        let db = database(FIXTURE);
+        assert_eq!(db.total_aliases(), 0);
+        assert_eq!(db.total_names(), db.len());
+
+        let db = database("http 80/tcp www www-http\nhttp 80/udp www\nnbp 2/ddp\n");
+        assert_eq!(db.total_aliases(), 3);
+        assert_eq!(db.total_names(), 6);
+    }
+
+    #[test]
+    fn lookup_by_name_and_alias() {
+        let db = database(
+            "\
+            http 80/tcp www www-http\n\
+            http 80/udp www www-http\n\
+            ",
+        );
+        assert_eq!(db.len(), 2);
+
+        assert_eq!(db.get_by_name("HTTP", Some("udp")), Some(&db.entries()[1]));
+        assert_eq!(db.get_by_alias("www-http", None), Some(&db.entries()[0]));
+        assert_eq!(db.get_by_alias("www", Some("sctp")), None);
+        assert_eq!(db.get_by_name("www", None), None);
+    }
+
+    #[test]
+    fn get_by_name_all() {
+        let db = database(FIXTURE);
+        let e = db.entries();
+        assert_eq!(
+            db.get_by_name_all("CompressNet"),
+            vec!(&e[4], &e[5], &e[6], &e[7])
+        );
+        assert_eq!(db.get_by_name_all("nbp"), vec!(&e[3]));
+        assert_eq!(db.get_by_name_all("http"), Vec::<&ServiceEntry>::new());
+    }
+
+    #[test]
+    fn subset_for() {
+        let db = database(FIXTURE);
+        let subset = db.subset_for(&["compressnet", "bogus"]);
+        assert_eq!(subset.entries(), &db.entries()[4..]);
+        assert_eq!(subset.get_by_port(3, Some("udp")), Some(&db.entries()[6]));
+        assert_eq!(subset.get_by_name("tcpmux", None), None);
+
+        let subset = db.subset_for(&["nbp", "rtmp", "NBP"]);
+        assert_eq!(
+            subset.iter_tuples().collect::<Vec<_>>(),
+            vec!(("nbp", 2, "ddp"), ("rtmp", 1, "ddp"))
+        );
+        assert!(db.subset_for(&[]).is_empty());
+    }
+
+    #[test]
+    fn port_of() {
+        let db = database("http 80/tcp www\nntp 123/udp\n");
+        assert_eq!(db.port_of("www", None), Some(80));
+        assert_eq!(db.port_of("ntp", Some("tcp")), None);
+        assert_eq!(db.port_or("http", Some("tcp"), 8080), 80);
+        assert_eq!(db.port_or("ntp", Some("tcp"), 1123), 1123);
+        assert_eq!(db.port_or("bogus", None, 9000), 9000);
+    }
+
+    #[test]
+    fn resolve_port_spec() {
+        let db = database("http 80/tcp www\nntp 123/udp\n");
+        assert_eq!(db.resolve_port_spec("80", None), Some(80));
+        assert_eq!(db.resolve_port_spec("8080", Some("udp")), Some(8080));
+        assert_eq!(db.resolve_port_spec("http", None), Some(80));
+        assert_eq!(db.resolve_port_spec("WWW", Some("tcp")), Some(80));
+        assert_eq!(db.resolve_port_spec("ntp", Some("tcp")), None);
+        assert_eq!(db.resolve_port_spec("bogus", None), None);
+        assert_eq!(db.resolve_port_spec("70000", None), None);
+    }
+
+    #[test]
+    fn lookup_policy() {
+        let contents = "http 80/tcp\nhttp 8080/tcp\nwww 80/tcp\nalt 8000-8100/tcp\n";
+        let opts = ParseOptions {
+            allow_port_ranges: true,
+            ..Default::default()
+        };
+        let entries = parse_reader(contents.as_bytes(), &opts).unwrap();
+        let first = ServiceDatabase::new(entries.clone());
+        assert_eq!(first.get_by_name("http", Some("tcp")).unwrap().port, 80);
+        assert_eq!(first.get_by_port(80, None).unwrap().name, "http");
+        assert_eq!(first.get_by_port(8080, None).unwrap().name, "http");
+
+        for build_case_folded_index in &[true, false] {
+            let opts = ParseOptions {
+                lookup_policy: LookupPolicy::LastWins,
+                build_case_folded_index: *build_case_folded_index,
+                ..Default::default()
+            };
+            let mut last = ServiceDatabase::with_options(entries.clone(), &opts);
+            assert_eq!(last.get_by_name("http", Some("tcp")).unwrap().port, 8080);
+            assert_eq!(last.get_by_port(80, None).unwrap().name, "www");
+            assert_eq!(last.get_by_port(8080, None).unwrap().name, "alt");
+            let entry = last.entry("http".to_string(), "tcp".to_string());
+            assert_eq!(entry.get().unwrap().port, 8080);
+        }
+    }
+
+    #[test]
+    fn canonical_name() {
+        let db = database("http 80/tcp www www-http\n");
+        assert_eq!(db.canonical_name_for("www"), Some("http"));
+        assert_eq!(db.canonical_name_for("WWW-HTTP"), Some("http"));
+        assert_eq!(db.canonical_name_for("http"), Some("http"));
+        assert_eq!(db.canonical_name_for("gopher"), None);
+    }
+
+    #[test]
+    fn retain_entries() {
+        let mut db = database(FIXTURE);
+        assert!(db.get_by_name("nbp", None).is_some());
+
+        db.retain(|entry| entry.protocol != "ddp");
+        assert_eq!(db.len(), 6);
+        assert!(db.entries().iter().all(|entry| entry.protocol != "ddp"));
+        assert_eq!(db.get_by_name("rtmp", None), None);
+        assert_eq!(db.get_by_name("nbp", None), None);
+        assert_eq!(db.get_by_name("compressnet", Some("ddp")), None);
+        assert_eq!(
+            db.get_by_name("tcpmux", Some("tcp")),
+            Some(&db.entries()[1])
+        );
+        assert_eq!(
+            db.get_by_name("compressnet", Some("tcp")),
+            Some(&db.entries()[3])
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let base = database("http 80/tcp www\nhttp 80/udp www\ngopher 70/tcp\n");
+        let db = ServiceDatabase::with_overrides(
+            base,
+            vec![
+                "HTTP 8080/TCP www".parse().unwrap(),
+                "redis 6379/tcp".parse().unwrap(),
+            ],
+        );
+
+        assert_eq!(db.len(), 4);
+        assert_eq!(db.get_by_name("http", Some("tcp")).unwrap().port, 8080);
+        assert_eq!(db.get_by_alias("www", None).unwrap().port, 8080);
+        assert_eq!(db.get_by_name("http", Some("udp")).unwrap().port, 80);
+        assert_eq!(db.get_by_port(80, Some("tcp")), None);
+        assert_eq!(db.get_by_name("gopher", None).unwrap().port, 70);
+        assert_eq!(db.get_by_name("redis", None).unwrap().port, 6379);
+    }
+
+    #[test]
+    fn lookup_without_name_index() {
+        let opts = ParseOptions {
+            build_case_folded_index: false,
+            ..Default::default()
+        };
+        let contents = format!(
+            "{}smtp 25/tcp mail\nsubmission 587/tcp mail\nhttp 80/tcp WWW\n",
+            FIXTURE
+        );
+        let entries = parse_reader(contents.as_bytes(), &opts).unwrap();
+        let indexed = ServiceDatabase::new(entries.clone());
+        let scanned = ServiceDatabase::with_options(entries, &opts);
+
+        for query in &["tcpmux", "COMPRESSNET", "mail", "www", "nbp", "gopher"] {
+            for protocol in &[None, Some("tcp"), Some("UDP"), Some("ddp")] {
+                assert_eq!(
+                    indexed.get_by_name(query, *protocol),
+                    scanned.get_by_name(query, *protocol)
+                );
+                assert_eq!(
+                    indexed.get_by_alias(query, *protocol),
+                    scanned.get_by_alias(query, *protocol)
+                );
+            }
+        }
+        assert_eq!(
+            scanned.get_by_alias("www", None),
+            Some(&scanned.entries()[10])
+        );
+        assert_eq!(
+            indexed.find_alias_collisions(),
+            scanned.find_alias_collisions()
+        );
+        assert_eq!(scanned.find_alias_collisions().len(), 1);
+    }
+
+    #[test]
+    fn from_name_port_pairs() {
+        let db = ServiceDatabase::from_name_port_pairs(vec![
+            ("http".to_string(), 80),
+            ("redis".to_string(), 6379),
+        ]);
+
+        assert_eq!(db.len(), 2);
+        assert_eq!(
+            db.get_by_name("redis", Some("tcp")),
+            Some(&"redis 6379/tcp".parse().unwrap())
+        );
+        assert_eq!(db.get_by_port(80, Some("udp")), None);
+
+        let db: ServiceDatabase = vec![("gopher".to_string(), 70)].into_iter().collect();
+        assert_eq!(db.get_by_port(70, None).unwrap().protocol, "tcp");
+    }
+
+    #[test]
+    fn collect_built_entries() {
+        let entries = vec![
+            ServiceEntry::builder("myapp", 9000, "tcp")
+                .alias("app")
+                .build()
+                .unwrap(),
+            ServiceEntry::builder("myapp-metrics", 9100, "udp")
+                .build()
+                .unwrap(),
+        ];
+        let db: ServiceDatabase = entries.clone().into_iter().collect();
+
+        assert_eq!(db.get_by_name("myapp", None), Some(&entries[0]));
+        assert_eq!(
+            db.get_by_name("MYAPP-metrics", Some("udp")),
+            Some(&entries[1])
+        );
+        assert_eq!(db.get_by_alias("app", Some("tcp")), Some(&entries[0]));
+        assert_eq!(db.get_by_port(9100, Some("udp")), Some(&entries[1]));
+        assert_eq!(db.get_by_port(9100, Some("tcp")), None);
+    }
+
+    #[test]
+    fn upsert_entry() {
+        let mut db = database(FIXTURE);
+
+        let entry = db
+            .entry("http".to_string(), "tcp".to_string())
+            .or_insert("http 80/tcp www".parse().unwrap());
+        assert_eq!(entry, "http 80/tcp www");
+        assert_eq!(db.len(), 9);
+        assert_eq!(db.get_by_alias("www", None), Some(&db.entries()[8]));
+
+        let entry = db
+            .entry("HTTP".to_string(), "TCP".to_string())
+            .or_insert_with(|| panic!("http is already present"));
+        assert_eq!(entry.port, 80);
+        assert_eq!(db.len(), 9);
+
+        db.entry("tcpmux".to_string(), "tcp".to_string())
+            .and_modify(|entry| entry.port = 4242);
+        assert_eq!(db.get_by_port(4242, None), Some(&db.entries()[2]));
+        assert_eq!(db.get_by_port(1, Some("tcp")), None);
+
+        // the modified entry keeps its place in file order under its new keys
+        db.entry("rtmp".to_string(), "ddp".to_string())
+            .and_modify(|entry| {
+                entry.name = "routing".to_string();
+                entry.port = 2;
+                entry.aliases.push("nbp".to_string());
+            });
+        assert_eq!(db.get_by_port(2, None), Some(&db.entries()[0]));
+        assert_eq!(db.get_by_port(1, None), Some(&db.entries()[1]));
+        assert_eq!(db.get_by_name("routing", None), Some(&db.entries()[0]));
+        assert_eq!(db.get_by_name("rtmp", None), None);
+        assert_eq!(db.get_by_alias("nbp", None), Some(&db.entries()[0]));
+        assert_eq!(db.get_by_name("nbp", None), Some(&db.entries()[3]));
+        assert!(db
+            .entry("gopher".to_string(), "tcp".to_string())
+            .and_modify(|_| panic!("gopher isn't present"))
+            .get()
+            .is_none());
+    }
+
+    #[test]
+    fn lookup_by_port() {
+        let db = database(FIXTURE);
+        assert_eq!(db.get_by_port(1, None), Some(&db.entries()[0]));
+        assert_eq!(db.get_by_port(1, Some("TCP")), Some(&db.entries()[2]));
+        assert_eq!(db.get_by_port(2, Some("sctp")), None);
+        assert_eq!(db.get_by_port(4, None), None);
+    }
+
+    #[test]
+    fn lookup_by_port_preferred() {
+        let db = database(FIXTURE);
+        assert_eq!(db.get_by_port_preferred(1), Some(&db.entries()[2]));
+        assert_eq!(db.get_by_port_preferred(1).unwrap().protocol, "tcp");
+
+        let db = database("rtmp 1/ddp\ntcpmux 1/udp\nnbp 2/ddp\n");
+        assert_eq!(db.get_by_port_preferred(1).unwrap().protocol, "udp");
+        assert_eq!(db.get_by_port_preferred(2).unwrap().name, "nbp");
+        assert_eq!(db.get_by_port_preferred(3), None);
+    }
+
+    #[test]
+    fn describe_port() {
+        let db = database(FIXTURE);
+        assert_eq!(
+            db.describe_port(3, "tcp"),
+            Some("compressnet (3/tcp)".to_string())
+        );
+        assert_eq!(db.describe_port(3, "ddp"), None);
+        assert_eq!(db.describe_port(6379, "tcp"), None);
+
+        let opts = ParseOptions {
+            keep_comments: true,
+            allow_port_ranges: true,
+            ..Default::default()
+        };
+        let contents = "ssh 22/tcp # Secure Shell owner=ops\nx11 6000-6063/tcp # X Window\n";
+        let db = ServiceDatabase::new(parse_reader(contents.as_bytes(), &opts).unwrap());
+        assert_eq!(
+            db.describe_port(22, "tcp"),
+            Some("ssh (22/tcp): Secure Shell".to_string())
+        );
+        assert_eq!(
+            db.describe_port(6010, "tcp"),
+            Some("x11 (6000-6063/tcp): X Window".to_string())
+        );
+    }
+
+    #[test]
+    fn protocols_by_frequency() {
+        let db = database(FIXTURE);
+        assert_eq!(db.protocol_counts()["ddp"], 2);
+        assert_eq!(
+            db.protocols_by_frequency(),
+            vec!(
+                ("tcp".to_string(), 3),
+                ("udp".to_string(), 3),
+                ("ddp".to_string(), 2)
+            )
+        );
+
+        let db = database("http 80/TCP\nhttp 80/udp\nhttp 80/Sctp\nhttp 80/tcp\n");
+        assert_eq!(
+            db.protocols_by_frequency(),
+            vec!(
+                ("tcp".to_string(), 2),
+                ("sctp".to_string(), 1),
+                ("udp".to_string(), 1)
+            )
+        );
+        assert!(ServiceDatabase::default()
+            .protocols_by_frequency()
+            .is_empty());
+    }
+
+    #[test]
+    fn count_by_class() {
+        let db = database(
+            "\
+            tcpmux 1/tcp\n\
+            http 80/tcp www\n\
+            http 80/udp www\n\
+            redis 6379/tcp\n\
+            ephemeral 49152/udp\n\
+            last 65535/tcp\n\
+            ",
+        );
+        let counts = db.count_by_class();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&PortClass::System], 3);
+        assert_eq!(counts[&PortClass::Registered], 1);
+        assert_eq!(counts[&PortClass::Dynamic], 2);
+
+        assert_eq!(
+            database(FIXTURE).count_by_class(),
+            vec!((PortClass::System, 8)).into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn next_free_registered_port() {
+        let db = database("a 1024/tcp\nb 1025/tcp\nc 1026/udp\n");
+        assert_eq!(db.next_free_registered_port("tcp"), Some(1026));
+        assert_eq!(db.next_free_registered_port("udp"), Some(1024));
+
+        let opts = ParseOptions {
+            allow_port_ranges: true,
+            ..Default::default()
+        };
+        let contents = "all 1024-49150/tcp\nlast 49151/tcp\n";
+        let db = ServiceDatabase::new(parse_reader(contents.as_bytes(), &opts).unwrap());
+        assert_eq!(db.next_free_registered_port("tcp"), None);
+        assert_eq!(db.next_free_registered_port("sctp"), Some(1024));
+    }
+
+    #[test]
+    fn find_gaps() {
+        let db = database(
+            "\
+            app-api 7000/tcp\n\
+            app-web 7001/tcp\n\
+            app-admin 7002/udp\n\
+            app-metrics 7003/tcp\n\
+            app-jobs 7004/tcp\n\
+            ",
+        );
+        assert_eq!(db.find_gaps(7000..=7004, "tcp"), vec!(7002));
+        assert_eq!(db.find_gaps(7001..=7003, "udp"), vec!(7001, 7003));
+        assert_eq!(db.find_gaps(7003..=7004, "TCP"), Vec::<u16>::new());
+
+        let opts = ParseOptions {
+            allow_port_ranges: true,
+            ..Default::default()
+        };
+        let contents = "app 7000-7002/tcp\napp-jobs 7004/tcp\n";
+        let db = ServiceDatabase::new(parse_reader(contents.as_bytes(), &opts).unwrap());
+        assert_eq!(db.find_gaps(7000..=7005, "tcp"), vec!(7003, 7005));
+    }
+
+    #[test]
+    fn flatten_aliases() {
+        let db = database("http 80/tcp www www-http\nnbp 2/ddp\n");
+        let pairs: Vec<_> = db
+            .flatten_aliases()
+            .map(|(name, entry)| (name, entry.port))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec!(("http", 80), ("www", 80), ("www-http", 80), ("nbp", 2))
+        );
+        assert_eq!(
+            db.flatten_aliases()
+                .filter(|(_, entry)| entry.name == "http")
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn lookup_by_port_range() {
+        let opts = ParseOptions {
+            allow_port_ranges: true,
+            ..Default::default()
+        };
+        let contents = "myservice 8000-8010/tcp\nalt 8005/tcp\nalt 8005/udp\n";
+        let db = ServiceDatabase::new(parse_reader(contents.as_bytes(), &opts).unwrap());
+
+        assert_eq!(db.get_by_port(8000, None), Some(&db.entries()[0]));
+        assert_eq!(db.get_by_port(8005, Some("tcp")), Some(&db.entries()[0]));
+        assert_eq!(db.get_by_port(8005, Some("udp")), Some(&db.entries()[2]));
+        assert_eq!(db.get_by_port(8010, Some("tcp")), Some(&db.entries()[0]));
+        assert_eq!(db.get_by_port(8011, None), None);
+    }
+
+    #[test]
+    fn to_services_file() {
+        let mut db = database(FIXTURE);
+        db.retain(|entry| entry.port != 3);
+        let mut entries = db.entries().to_vec();
+        entries.push("http 80/tcp www www-http".parse().unwrap());
+        let db = ServiceDatabase::new(entries);
+
+        let output = db.to_services_file(true);
+        assert!(output.starts_with("# /etc/services:\n#\n# Network services, Internet style\n"));
+        assert!(output.ends_with("http            80/tcp          www www-http\n"));
+        assert_eq!(database(&output).entries(), db.entries());
+
+        let output = db.to_services_file(false);
+        assert!(output.starts_with("rtmp            1/ddp\n"));
+        assert_eq!(database(&output).entries(), db.entries());
+
+        let mut entries = db.entries().to_vec();
+        entries.push(ServiceEntry {
+            name: "my service".to_string(),
+            port: 8080,
+            protocol: "tcp".to_string(),
+            aliases: vec!["alt name".to_string()],
+            comment: Some("local\nonly".to_string()),
+            ..Default::default()
+        });
+        let output = ServiceDatabase::new(entries).to_services_file(false);
+        assert!(output.ends_with("my_service      8080/tcp        alt_name # local only\n"));
+        assert_eq!(database(&output).len(), db.len() + 1);
+    }
+
+    #[test]
+    fn to_sorted_services_file() {
+        let mut entries = database(FIXTURE).entries().to_vec();
+        entries.insert(0, "http 80/tcp www".parse().unwrap());
+        let db = ServiceDatabase::new(entries);
+
+        let output = db.to_sorted_services_file(false);
+        let port_protocols: Vec<_> = database(&output)
+            .entries()
+            .iter()
+            .map(|entry| entry.port_protocol())
+            .collect();
+        assert_eq!(
+            port_protocols,
+            vec!("1/ddp", "1/tcp", "1/udp", "2/ddp", "2/tcp", "2/udp", "3/tcp", "3/udp", "80/tcp")
+        );
+        assert!(db
+            .to_sorted_services_file(true)
+            .starts_with("# /etc/services:\n"));
+    }
+
+    #[test]
+    fn overlong_names() {
+        let long_name = "x".repeat(64);
+        let db = database(&format!(
+            "http 80/tcp\n{} 8080/tcp\n{} 8081/tcp\n",
+            long_name,
+            &long_name[1..]
+        ));
+
+        assert_eq!(
+            db.find_overlong_names(ServiceDatabase::DEFAULT_MAX_NAME_LEN),
+            vec!(&db.entries()[1])
+        );
+        assert_eq!(db.find_overlong_names(4).len(), 2);
+        assert!(database(FIXTURE)
+            .find_overlong_names(ServiceDatabase::DEFAULT_MAX_NAME_LEN)
+            .is_empty());
+    }
+
+    #[test]
+    fn rebuild_indexes() {
+        let mut db = database(FIXTURE);
+        db.entries_mut().retain(|entry| entry.name != "tcpmux");
+        db.entries_mut()[0].name = "routing".to_string();
+        db.entries_mut().push("http 80/tcp www".parse().unwrap());
+        db.rebuild_indexes();
+
+        assert_eq!(db.get_by_name("rtmp", None), None);
+        assert_eq!(db.get_by_name("routing", None).unwrap().port, 1);
+        assert_eq!(db.get_by_port(1, None).unwrap().name, "routing");
+        assert_eq!(db.get_by_name("tcpmux", None), None);
+        assert_eq!(db.get_by_alias("www", None).unwrap().port, 80);
+        assert_eq!(db.get_by_port(80, Some("tcp")).unwrap().name, "http");
+    }
+
+    #[test]
+    fn with_capacity() {
+        let mut db = ServiceDatabase::with_capacity(100);
+        assert!(db.is_empty());
+        assert!(db.entries.capacity() >= 100);
+        assert!(db.aliases.capacity() >= 25);
+
+        db.entry("http".to_string(), "tcp".to_string())
+            .or_insert("http 80/tcp".parse().unwrap());
+        assert_eq!(db.port_of("http", None), Some(80));
+    }
+
+    #[test]
+    fn entries_in_port_range() {
+        let db = database(FIXTURE);
+        let ports: Vec<_> = db
+            .entries_in_port_range(1, 2)
+            .iter()
+            .map(|entry| entry.port)
+            .collect();
+        assert_eq!(ports, vec!(1, 1, 1, 2, 2, 2));
+        assert_eq!(db.entries_in_port_range(3, 3).len(), 2);
+        assert!(db.entries_in_port_range(3, 1).is_empty());
+
+        let opts = ParseOptions {
+            allow_port_ranges: true,
+            ..Default::default()
+        };
+        let entries = parse_reader("x11 6000-6063/tcp\n".as_bytes(), &opts).unwrap();
+        let db = ServiceDatabase::new(entries);
+        assert_eq!(db.entries_in_port_range(6010, 7000).len(), 1);
+        assert!(db.entries_in_port_range(1024, 5999).is_empty());
+    }
+
+    #[test]
+    fn filter() {
+        let db = database(FIXTURE);
+        let udp = db.filter(|entry| entry.protocol == "udp");
+        let names: Vec<_> = udp.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!("tcpmux", "compressnet", "compressnet"));
+        assert!(db.filter(|_| false).is_empty());
+    }
+
+    #[test]
+    fn search_description() {
+        let contents = "\
+            smtp 25/tcp mail # Simple Mail Transfer\n\
+            http 80/tcp www # World Wide Web HTTP\n\
+            pop3 110/tcp pop-3 # POP version 3, for MAIL\n\
+            ssh 22/tcp\n\
+        ";
+        let opts = ParseOptions {
+            keep_comments: true,
+            ..Default::default()
+        };
+        let db = ServiceDatabase::new(parse_reader(contents.as_bytes(), &opts).unwrap());
+        let names: Vec<_> = db
+            .search_description("Mail")
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+        assert_eq!(names, vec!("smtp", "pop3"));
+        assert!(db.search_description("ftp").is_empty());
+
+        assert!(database(contents).search_description("mail").is_empty());
+    }
+
+    #[test]
+    fn port_protocol_pairs() {
+        let pairs: Vec<_> = database(FIXTURE)
+            .port_protocol_pairs()
+            .into_iter()
+            .collect();
+        let expected: Vec<_> = [
+            (1, "ddp"),
+            (1, "tcp"),
+            (1, "udp"),
+            (2, "ddp"),
+            (2, "tcp"),
+            (2, "udp"),
+            (3, "tcp"),
+            (3, "udp"),
+        ]
+        .iter()
+        .map(|&(port, protocol)| (port, protocol.to_string()))
+        .collect();
+        assert_eq!(pairs, expected);
+
+        let db = database("http 80/tcp\nwww 80/tcp\n");
+        assert_eq!(db.port_protocol_pairs().len(), 1);
+    }
+
+    #[test]
+    fn port_name_map() {
+        let map = database(FIXTURE).port_name_map("tcp");
+        let expected: HashMap<_, _> = vec![
+            (1, "tcpmux".to_string()),
+            (2, "compressnet".to_string()),
+            (3, "compressnet".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(map, expected);
+
+        let db = database("http 80/tcp\nwww 80/tcp\n");
+        assert_eq!(db.port_name_map("TCP")[&80], "http");
+        let opts = ParseOptions {
+            lookup_policy: LookupPolicy::LastWins,
+            ..Default::default()
+        };
+        let db = ServiceDatabase::with_options(db.entries().to_vec(), &opts);
+        assert_eq!(db.port_name_map("tcp")[&80], "www");
+        assert!(db.port_name_map("udp").is_empty());
+    }
+
+    #[test]
+    fn verify_against() {
+        let reference = database("ssh 22/tcp\nssh 22/udp\ndomain 53/udp\n");
+        let db = database("ssh 2222/tcp\nSSH 22/udp\ndomain 53/tcp\nredis 6379/tcp\n");
+        let mismatches = db.verify_against(&reference);
+        assert_eq!(
+            mismatches,
+            vec!(
+                PortMismatch {
+                    entry: &db.entries()[0],
+                    expected: &reference.entries()[0],
+                },
+                PortMismatch {
+                    entry: &db.entries()[2],
+                    expected: &reference.entries()[2],
+                },
+            )
+        );
+
+        assert!(reference.verify_against(&reference).is_empty());
+    }
+
+    #[test]
+    fn find_shadowed_well_known() {
+        let iana = database("ssh 22/tcp\nhttp-alt 8080/tcp\n");
+        let db = database("ssh 50000/tcp\nssh 2222/tcp\nhttp-alt 50001/tcp\nmine 50002/tcp\n");
+        assert_eq!(db.find_shadowed_well_known(&iana), vec!(&db.entries()[0]));
+        assert!(iana.find_shadowed_well_known(&iana).is_empty());
+    }
+
+    #[test]
+    fn iter_tuples() {
+        let db = database(FIXTURE);
+        let tuples: Vec<_> = db
+            .iter_tuples()
+            .map(|(name, port, protocol)| (port, name, protocol))
+            .collect();
+        assert_eq!(tuples, db.rows());
+        assert_eq!(db.iter_tuples().next(), Some(("rtmp", 1, "ddp")));
+    }
+
+    #[test]
+    fn explain_lookup() {
+        let db = database("http 80/tcp www\nWWW-ldap 80/udp\n");
+        let explain = |query| {
+            let explanation = db.explain_lookup(query, None);
+            assert!(explanation.indexed);
+            (
+                explanation.entry.map(|entry| entry.port),
+                explanation.matched_via,
+            )
+        };
+        assert_eq!(explain("http"), (Some(80), MatchedVia::Name));
+        assert_eq!(explain("www"), (Some(80), MatchedVia::Alias));
+        assert_eq!(explain("HTTP"), (Some(80), MatchedVia::CaseFolded));
+        assert_eq!(explain("www-ldap"), (Some(80), MatchedVia::CaseFolded));
+        assert_eq!(explain("ftp"), (None, MatchedVia::NotFound));
+
+        let opts = ParseOptions {
+            build_case_folded_index: false,
+            ..Default::default()
+        };
+        let db = ServiceDatabase::with_options(db.entries().to_vec(), &opts);
+        let explanation = db.explain_lookup("www", Some("tcp"));
+        assert_eq!(explanation.matched_via, MatchedVia::Alias);
+        assert!(!explanation.indexed);
+    }
+
+    #[test]
+    fn rows() {
+        let db = database(FIXTURE);
+        let rows = db.rows();
+        assert_eq!(rows.len(), db.len());
+        assert_eq!(rows[0], (1, "rtmp", "ddp"));
+        assert_eq!(rows[7], (3, "compressnet", "tcp"));
+    }
+
+    #[test]
+    fn to_simple_table() {
+        let db = database("http 80/tcp www www-http\ntcpmux 1/tcp\nhttp 80/udp www\n");
+        assert_eq!(
+            db.to_simple_table(),
+            "tcpmux\t1/tcp\nhttp\t80/tcp\nhttp\t80/udp\n"
+        );
+        assert_eq!(ServiceDatabase::default().to_simple_table(), "");
+    }
+
+    #[test]
+    fn alias_collisions() {
+        let db = database(
+            "\
+            smtp 25/tcp mail\n\
+            smtp 25/udp mail\n\
+            submission 587/tcp mail msa\n\
+            http 80/tcp www\n\
+            http 80/udp www\n\
+            msa 2000/tcp\n\
+            ",
+        );
+        let e = db.entries();
+
+        assert_eq!(
+            db.find_alias_collisions(),
+            vec!(
+                ("mail".to_string(), vec!(&e[0], &e[1], &e[2])),
+                ("msa".to_string(), vec!(&e[2], &e[5])),
+            )
+        );
+
+        assert_eq!(database(FIXTURE).find_alias_collisions(), vec!());
+    }
+
+    #[test]
+    fn alias_usage_count() {
+        let contents = "\
+            smtp 25/tcp mail\n\
+            smtp 25/udp mail\n\
+            submission 587/tcp MAIL msa\n\
+            http 80/tcp www\n\
+            ";
+        let opts = ParseOptions {
+            build_case_folded_index: false,
+            ..Default::default()
+        };
+        let entries = parse_reader(contents.as_bytes(), &opts).unwrap();
+        for db in &[
+            ServiceDatabase::new(entries.clone()),
+            ServiceDatabase::with_options(entries, &opts),
+        ] {
+            assert_eq!(db.alias_usage_count("mail"), 2);
+            assert_eq!(db.alias_usage_count("Mail"), 2);
+            assert_eq!(db.alias_usage_count("www"), 1);
+            assert_eq!(db.alias_usage_count("http"), 0);
+        }
+    }
+
+    #[test]
+    fn name_port_anomalies() {
+        let db = database(
+            "\
+            http 80/tcp www\n\
+            http 80/udp www\n\
+            ssh 22/tcp\n\
+            HTTP 8080/tcp\n\
+            ",
+        );
+        assert_eq!(
+            db.find_name_port_anomalies(),
+            vec!(("http".to_string(), vec!(80, 8080)))
+        );
+
+        assert_eq!(
+            database("http 80/tcp\nhttp 80/udp\nssh 22/tcp\n").find_name_port_anomalies(),
+            vec!()
+        );
+    }
+
+    #[test]
+    fn validate_unique_canonical() {
+        assert_eq!(
+            database(FIXTURE).validate_unique_canonical(),
+            Err(vec!("compressnet".to_string()))
+        );
+        assert_eq!(
+            database("http 80/tcp\nhttp 80/udp\nssh 22/tcp\n").validate_unique_canonical(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn well_known() {
+        let iana = database(FIXTURE);
+        let entry = |line: &str| line.parse::<ServiceEntry>().unwrap();
+
+        assert!(entry("tcpmux 1/tcp").is_well_known(&iana));
+        assert!(entry("CompressNet 3/udp mux").is_well_known(&iana));
+        assert!(!entry("tcpmux 1/ddp").is_well_known(&iana));
+        assert!(!entry("compressnet 4/tcp").is_well_known(&iana));
+        assert!(!entry("my-service 1/tcp").is_well_known(&iana));
+    }
+
+    #[test]
+    fn shrink() {
+        let contents: String = (1..=1000)
+            .map(|port| format!("service-{} {}/tcp alias-{}\n", port, port, port))
+            .collect();
+        let mut db = database(&contents);
+        db.retain(|entry| entry.port <= 10);
+        let capacity = db.entries.capacity();
+
+        db.shrink();
+        assert!(db.entries.capacity() < capacity);
+        assert!(db.names.capacity() < 1000);
+        assert_eq!(db.len(), 10);
+        assert_eq!(db.get_by_alias("alias-10", None), Some(&db.entries()[9]));
+    }
+
+    #[test]
+    fn collect_entries() {
+        let opts = ParseOptions::default();
+        let db: Result<ServiceDatabase, _> = parse_reader_iter(FIXTURE.as_bytes(), &opts).collect();
+        assert_eq!(db.unwrap().entries(), database(FIXTURE).entries());
+
+        let contents = "http 80/tcp\nbad\nssh 22/tcp\n";
+        let db: Result<ServiceDatabase, _> =
+            parse_reader_iter(contents.as_bytes(), &opts).collect();
+        assert_eq!(
+            db.unwrap_err(),
+            ParseError::MissingPortProtocol("bad".to_string())
+        );
+    }
+}
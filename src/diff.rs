@@ -0,0 +1,337 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use crate::{ServiceDatabase, ServiceEntry, ServiceFileWriter};
+
+/// The differences between two databases, see `ServiceDatabase::diff`
+/// Entries are matched up by name and protocol, ignoring case.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServicesDiff {
+    /// Entries only in the new database
+    pub added: Vec<ServiceEntry>,
+    /// Entries only in the old database
+    pub removed: Vec<ServiceEntry>,
+    /// `(old, new)` pairs of entries with the same name and protocol but a different port or
+    /// aliases
+    pub changed: Vec<(ServiceEntry, ServiceEntry)>,
+}
+
+impl ServicesDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+type Key = (String, String);
+
+fn key(entry: &ServiceEntry) -> Key {
+    (entry.name.to_lowercase(), entry.protocol.to_lowercase())
+}
+
+// Entries with the same key are equivalent if they cover the same ports, have the same protocol
+// suffix and have the same aliases in any order
+fn equivalent(a: &ServiceEntry, b: &ServiceEntry) -> bool {
+    let sorted_aliases = |entry: &ServiceEntry| {
+        let mut aliases = entry.aliases.clone();
+        aliases.sort();
+        aliases
+    };
+
+    a.port == b.port
+        && a.port_range == b.port_range
+        && a.protocol_suffix == b.protocol_suffix
+        && sorted_aliases(a) == sorted_aliases(b)
+}
+
+fn group(entries: &[ServiceEntry]) -> HashMap<Key, Vec<&ServiceEntry>> {
+    let mut groups: HashMap<_, Vec<_>> = HashMap::new();
+    for entry in entries {
+        groups.entry(key(entry)).or_default().push(entry);
+    }
+    groups
+}
+
+impl ServiceDatabase {
+    /// Compare this database to `new`, ignoring the order of entries and of aliases
+    /// Added and removed entries are listed in the order of the database they're from, and a
+    /// name/protocol defined by several entries may be reported more than once.
+    pub fn diff(&self, new: &ServiceDatabase) -> ServicesDiff {
+        let old_groups = group(self.entries());
+        let new_groups = group(new.entries());
+
+        let mut diff = ServicesDiff::default();
+        let mut seen = HashSet::new();
+        for entry in self.entries().iter().chain(new.entries()) {
+            let key = key(entry);
+            if !seen.insert(key.clone()) {
+                continue;
+            }
+
+            let mut removed = old_groups.get(&key).cloned().unwrap_or_default();
+            let mut added = new_groups.get(&key).cloned().unwrap_or_default();
+            removed.retain(|old_entry| {
+                match added
+                    .iter()
+                    .position(|new_entry| equivalent(old_entry, new_entry))
+                {
+                    Some(idx) => {
+                        added.remove(idx);
+                        false
+                    }
+                    None => true,
+                }
+            });
+
+            // whatever is left over on both sides was changed
+            let paired = removed.len().min(added.len());
+            diff.changed.extend(
+                removed
+                    .iter()
+                    .zip(&added)
+                    .map(|(&old_entry, &new_entry)| (old_entry.clone(), new_entry.clone())),
+            );
+            diff.removed
+                .extend(removed[paired..].iter().copied().cloned());
+            diff.added.extend(added[paired..].iter().copied().cloned());
+        }
+
+        diff
+    }
+}
+
+// An entry as it would be written on one line, without padding or its comment
+fn summary_line(entry: &ServiceEntry) -> String {
+    let mut line = format!("{} {}", entry.name, entry.port_protocol());
+    for alias in &entry.aliases {
+        line.push(' ');
+        line.push_str(alias);
+    }
+    line
+}
+
+impl ServiceDatabase {
+    /// Describe the differences between `old` and `new` found by `diff`, one entry per line
+    /// under `added:`, `removed:` and `changed:` headings, e.g. to log how a package update
+    /// changed /etc/services
+    /// A changed port is written as `http: 80/tcp -> 8080/tcp`, followed by any added or
+    /// removed aliases. Headings without entries are left out, and identical databases are
+    /// described as `no changes`.
+    pub fn diff_summary(old: &ServiceDatabase, new: &ServiceDatabase) -> String {
+        let diff = old.diff(new);
+        if diff.is_empty() {
+            return "no changes\n".to_string();
+        }
+
+        let mut summary = String::new();
+        let mut section = |heading: &str, lines: Vec<String>| {
+            if lines.is_empty() {
+                return;
+            }
+            summary.push_str(heading);
+            summary.push_str(":\n");
+            for line in lines {
+                summary.push_str("  ");
+                summary.push_str(&line);
+                summary.push('\n');
+            }
+        };
+        section("added", diff.added.iter().map(summary_line).collect());
+        section("removed", diff.removed.iter().map(summary_line).collect());
+
+        let changed = diff
+            .changed
+            .iter()
+            .map(|(old_entry, new_entry)| {
+                let mut changes = Vec::new();
+                let (old_ports, new_ports) = (old_entry.port_protocol(), new_entry.port_protocol());
+                if old_ports == new_ports {
+                    changes.push(old_ports);
+                } else {
+                    changes.push(format!("{} -> {}", old_ports, new_ports));
+                }
+                let (added, removed) = old_entry.alias_diff(new_entry);
+                if !added.is_empty() {
+                    changes.push(format!("added aliases {}", added.join(" ")));
+                }
+                if !removed.is_empty() {
+                    changes.push(format!("removed aliases {}", removed.join(" ")));
+                }
+                format!("{}: {}", new_entry.name, changes.join(", "))
+            })
+            .collect();
+        section("changed", changed);
+
+        summary
+    }
+}
+
+// Entries are the same service for set operations if they have the same name, protocol and port
+fn set_key(entry: &ServiceEntry) -> (String, String, u16) {
+    let (name, protocol) = key(entry);
+    (name, protocol, entry.port)
+}
+
+impl ServiceDatabase {
+    /// The entries of this database which are also in `other`, comparing the name and protocol
+    /// ignoring case, and the port
+    pub fn intersect(&self, other: &ServiceDatabase) -> Vec<&ServiceEntry> {
+        let keys: HashSet<_> = other.entries().iter().map(set_key).collect();
+        self.entries()
+            .iter()
+            .filter(|entry| keys.contains(&set_key(entry)))
+            .collect()
+    }
+
+    /// The entries of this database which aren't in `other`, compared like `intersect`
+    pub fn difference(&self, other: &ServiceDatabase) -> Vec<&ServiceEntry> {
+        let keys: HashSet<_> = other.entries().iter().map(set_key).collect();
+        self.entries()
+            .iter()
+            .filter(|entry| !keys.contains(&set_key(entry)))
+            .collect()
+    }
+}
+
+/// Check that two databases hold the same entries, ignoring the order of entries and of aliases
+/// e.g. to check in CI that a generated services file matches a golden copy
+pub fn assert_equivalent(a: &ServiceDatabase, b: &ServiceDatabase) -> Result<(), ServicesDiff> {
+    let diff = a.diff(b);
+    if diff.is_empty() {
+        Ok(())
+    } else {
+        Err(diff)
+    }
+}
+
+/// Write the entries of `updated` which are new or changed compared to `base`, as an overlay to
+/// be read over `base`, e.g. with `ServiceDatabase::with_overrides`
+/// Entries removed from `base` can't be expressed in an overlay, so they're written as commented
+/// out lines for the reader's benefit.
+pub fn write_patch<W: Write>(
+    w: &mut W,
+    base: &ServiceDatabase,
+    updated: &ServiceDatabase,
+) -> io::Result<()> {
+    let diff = base.diff(updated);
+    let mut writer = ServiceFileWriter::new(w);
+    for (_, new_entry) in &diff.changed {
+        writer.write_entry(new_entry)?;
+    }
+    for entry in &diff.added {
+        writer.write_entry(entry)?;
+    }
+
+    for entry in &diff.removed {
+        let mut line = ServiceFileWriter::new(Vec::new());
+        line.write_entry(entry)?;
+        writer.write_comment(&String::from_utf8_lossy(&line.into_inner()))?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_reader, ParseOptions};
+
+    fn database(contents: &str) -> ServiceDatabase {
+        ServiceDatabase::new(parse_reader(contents.as_bytes(), &ParseOptions::default()).unwrap())
+    }
+
+    fn entry(line: &str) -> ServiceEntry {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn equivalent_when_reordered() {
+        let a = database("http 80/tcp www www-http\ntcpmux 1/tcp\nhttp 80/udp www\n");
+        let b = database("HTTP 80/udp www\nhttp 80/TCP www-http www\ntcpmux 1/tcp # mux\n");
+
+        assert!(a.diff(&b).is_empty());
+        assert_eq!(assert_equivalent(&a, &b), Ok(()));
+    }
+
+    #[test]
+    fn different_databases() {
+        let old = database("http 80/tcp www\ngopher 70/tcp\nssh 22/tcp\n");
+        let new = database("ssh 22/tcp\nhttp 8080/tcp www\nredis 6379/tcp\n");
+
+        let diff = assert_equivalent(&old, &new).unwrap_err();
+        assert_eq!(
+            diff,
+            ServicesDiff {
+                added: vec!(entry("redis 6379/tcp")),
+                removed: vec!(entry("gopher 70/tcp")),
+                changed: vec!((entry("http 80/tcp www"), entry("http 8080/tcp www"))),
+            }
+        );
+        assert_eq!(new.diff(&old).added, vec!(entry("gopher 70/tcp")));
+    }
+
+    #[test]
+    fn diff_summary() {
+        let old = database("http 80/tcp www\ngopher 70/tcp\nssh 22/tcp\nntp 123/udp\n");
+        let new = database("ssh 22/tcp\nhttp 8080/tcp www\nredis 6379/tcp\nntp 123/udp time\n");
+
+        assert_eq!(
+            ServiceDatabase::diff_summary(&old, &new),
+            "\
+            added:\n  redis 6379/tcp\n\
+            removed:\n  gopher 70/tcp\n\
+            changed:\n  http: 80/tcp -> 8080/tcp\n  ntp: 123/udp, added aliases time\n\
+            "
+        );
+        assert_eq!(
+            ServiceDatabase::diff_summary(&new, &old),
+            "\
+            added:\n  gopher 70/tcp\n\
+            removed:\n  redis 6379/tcp\n\
+            changed:\n  http: 8080/tcp -> 80/tcp\n  ntp: 123/udp, removed aliases time\n\
+            "
+        );
+        assert_eq!(ServiceDatabase::diff_summary(&old, &old), "no changes\n");
+    }
+
+    #[test]
+    fn patch() {
+        let base = database("http 80/tcp www\ngopher 70/tcp\nssh 22/tcp\n");
+        let updated = database("ssh 22/tcp\nhttp 8080/tcp www\nredis 6379/tcp\n");
+
+        let mut patch = Vec::new();
+        write_patch(&mut patch, &base, &updated).unwrap();
+        let patch = String::from_utf8(patch).unwrap();
+        assert_eq!(
+            patch,
+            "\
+            http            8080/tcp        www\n\
+            redis           6379/tcp\n\
+            # gopher          70/tcp\n\
+            "
+        );
+
+        let overlay = parse_reader(patch.as_bytes(), &ParseOptions::default()).unwrap();
+        let patched = ServiceDatabase::with_overrides(base, overlay);
+        assert_eq!(patched.get_by_name("http", None).unwrap().port, 8080);
+        assert!(patched.get_by_name("redis", None).is_some());
+
+        let mut patch = Vec::new();
+        write_patch(&mut patch, &updated, &updated).unwrap();
+        assert!(patch.is_empty());
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = database("http 80/tcp www\ngopher 70/tcp\nssh 22/tcp\nhttp 80/udp\n");
+        let b = database("ssh 22/tcp\nHTTP 80/TCP\nhttp 8080/udp\nredis 6379/tcp\n");
+
+        let e = a.entries();
+        assert_eq!(a.intersect(&b), vec!(&e[0], &e[2]));
+        assert_eq!(a.difference(&b), vec!(&e[1], &e[3]));
+
+        let e = b.entries();
+        assert_eq!(b.intersect(&a), vec!(&e[0], &e[1]));
+        assert_eq!(b.difference(&a), vec!(&e[2], &e[3]));
+        assert_eq!(b.difference(&b), Vec::<&ServiceEntry>::new());
+    }
+}
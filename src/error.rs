@@ -0,0 +1,275 @@
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+/// The ways parsing a services file can fail
+/// Some variants only exist with the `std` or `cache` features, so that enabling a feature
+/// doesn't break matches in other crates, the enum is non-exhaustive.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ParseError {
+    /// The file could not be opened or read
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// The path does not exist, or is a symlink to a path that does not exist
+    #[cfg(feature = "std")]
+    FileNotFound { path: PathBuf },
+    /// The path exists but is a directory, socket, fifo or other non-regular file
+    #[cfg(feature = "std")]
+    NotARegularFile { path: PathBuf },
+    /// The line does not start with a service name, holds the first token of the line
+    MalformedInput(String),
+    /// The line starts with a port/protocol field instead of a service name, as on some systems
+    /// which omit the name, holds the port/protocol field
+    MissingServiceName(String),
+    /// The line has a service name but no port/protocol field, holds the service name
+    MissingPortProtocol(String),
+    /// The port is not a number, holds the port
+    MalformedPort(String),
+    /// The port/protocol field has no protocol, holds the text before the missing protocol
+    MissingProtocol(String),
+    /// An `include` directive refers to a file which is already being parsed
+    #[cfg(feature = "std")]
+    IncludeCycle { path: PathBuf },
+    /// `include` directives are nested too deeply
+    #[cfg(feature = "std")]
+    IncludeDepthExceeded { path: PathBuf },
+    /// A `+name` merge marker refers to a service which hasn't been defined yet, holds the name,
+    /// see `ParseOptions::allow_merge_markers`
+    UnknownMergeTarget(String),
+    /// The input has more entries than `ParseOptions::max_total_entries`, holds the limit
+    TooManyEntries(usize),
+    /// A field of a constructed entry can't be represented in a services file
+    InvalidField { field: &'static str, value: String },
+    /// A cache file was written by an incompatible version of this crate
+    #[cfg(feature = "cache")]
+    CacheVersionMismatch { found: u32, expected: u32 },
+    /// A cache file could not be decoded
+    #[cfg(feature = "cache")]
+    InvalidCache(String),
+}
+
+/// An error found on a particular line, see `validate_file_streaming`
+#[derive(Debug, PartialEq)]
+pub struct ParseErrorAt {
+    /// The 1-based line number, or 0 if the error isn't tied to a line, e.g. the file couldn't be
+    /// opened
+    pub line: usize,
+    pub error: ParseError,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            ParseError::Io(_) => write!(f, "error reading services file"),
+            #[cfg(feature = "std")]
+            ParseError::FileNotFound { path } => {
+                write!(f, "file \"{}\" does not exist", path.display())
+            }
+            #[cfg(feature = "std")]
+            ParseError::NotARegularFile { path } => {
+                write!(f, "\"{}\" is not a regular file", path.display())
+            }
+            #[cfg(feature = "std")]
+            ParseError::IncludeCycle { path } => {
+                write!(f, "include cycle through \"{}\"", path.display())
+            }
+            #[cfg(feature = "std")]
+            ParseError::IncludeDepthExceeded { path } => {
+                write!(f, "includes nested too deeply at \"{}\"", path.display())
+            }
+            ParseError::MalformedInput(token) => {
+                write!(f, "expected a service name, found \"{}\"", token)
+            }
+            ParseError::MissingServiceName(field) => {
+                write!(f, "missing service name before \"{}\"", field)
+            }
+            ParseError::MissingPortProtocol(name) => {
+                write!(f, "missing port/protocol after service \"{}\"", name)
+            }
+            ParseError::MalformedPort(port) => write!(f, "malformed port \"{}\"", port),
+            ParseError::MissingProtocol(before) => {
+                write!(f, "missing protocol after \"{}\"", before)
+            }
+            ParseError::UnknownMergeTarget(name) => {
+                write!(f, "cannot merge into undefined service \"{}\"", name)
+            }
+            ParseError::TooManyEntries(max) => write!(f, "more than {} entries", max),
+            ParseError::InvalidField { field, value } => {
+                write!(f, "invalid {} \"{}\"", field, value)
+            }
+            #[cfg(feature = "cache")]
+            ParseError::CacheVersionMismatch { found, expected } => write!(
+                f,
+                "cache has version {}, expected version {}",
+                found, expected
+            ),
+            #[cfg(feature = "cache")]
+            ParseError::InvalidCache(reason) => write!(f, "invalid cache: {}", reason),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseErrorAt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for ParseErrorAt {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for ParseError {
+    fn from(err: io::Error) -> Self {
+        ParseError::Io(err)
+    }
+}
+
+/// `io::Error` can't be compared, so two `Io` errors are equal if they have the same kind
+impl PartialEq for ParseError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            #[cfg(feature = "std")]
+            (ParseError::Io(a), ParseError::Io(b)) => a.kind() == b.kind(),
+            #[cfg(feature = "std")]
+            (ParseError::FileNotFound { path: a }, ParseError::FileNotFound { path: b })
+            | (ParseError::NotARegularFile { path: a }, ParseError::NotARegularFile { path: b })
+            | (ParseError::IncludeCycle { path: a }, ParseError::IncludeCycle { path: b })
+            | (
+                ParseError::IncludeDepthExceeded { path: a },
+                ParseError::IncludeDepthExceeded { path: b },
+            ) => a == b,
+            (ParseError::MalformedInput(a), ParseError::MalformedInput(b))
+            | (ParseError::MissingServiceName(a), ParseError::MissingServiceName(b))
+            | (ParseError::MissingPortProtocol(a), ParseError::MissingPortProtocol(b))
+            | (ParseError::MalformedPort(a), ParseError::MalformedPort(b))
+            | (ParseError::MissingProtocol(a), ParseError::MissingProtocol(b))
+            | (ParseError::UnknownMergeTarget(a), ParseError::UnknownMergeTarget(b)) => a == b,
+            (ParseError::TooManyEntries(a), ParseError::TooManyEntries(b)) => a == b,
+            (
+                ParseError::InvalidField { field: a, value: x },
+                ParseError::InvalidField { field: b, value: y },
+            ) => a == b && x == y,
+            #[cfg(feature = "cache")]
+            (
+                ParseError::CacheVersionMismatch {
+                    found: a,
+                    expected: x,
+                },
+                ParseError::CacheVersionMismatch {
+                    found: b,
+                    expected: y,
+                },
+            ) => a == b && x == y,
+            #[cfg(feature = "cache")]
+            (ParseError::InvalidCache(a), ParseError::InvalidCache(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate anyhow;
+
+    use std::io;
+
+    use super::*;
+
+    #[test]
+    fn io_error_source() {
+        let err = ParseError::from(io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+        let source = err.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::PermissionDenied);
+
+        assert!(ParseError::MalformedPort("asdf".to_string())
+            .source()
+            .is_none());
+    }
+
+    #[test]
+    fn anyhow_downcast() {
+        let err = anyhow::Error::new(ParseError::Io(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "eof",
+        )))
+        .context("Could not load services");
+
+        let parse_err = err.downcast_ref::<ParseError>().unwrap();
+        match parse_err {
+            ParseError::Io(io_err) => assert_eq!(io_err.kind(), io::ErrorKind::UnexpectedEof),
+            _ => panic!("expected an Io error"),
+        }
+
+        let kinds: Vec<_> = err
+            .chain()
+            .filter_map(|cause| cause.downcast_ref::<io::Error>())
+            .map(|cause| cause.kind())
+            .collect();
+        assert_eq!(kinds, vec!(io::ErrorKind::UnexpectedEof));
+    }
+
+    fn display(line: &str) -> String {
+        line.parse::<crate::ServiceEntry>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn display_messages() {
+        assert_eq!(
+            display("#http 80/tcp"),
+            "expected a service name, found \"#http\""
+        );
+        assert_eq!(
+            display("http"),
+            "missing port/protocol after service \"http\""
+        );
+        assert_eq!(display("http asdf/tcp"), "malformed port \"asdf\"");
+        assert_eq!(display("http 80/"), "missing protocol after \"80/\"");
+        assert_eq!(display("http 80"), "missing protocol after \"80\"");
+
+        assert_eq!(
+            ParseError::FileNotFound {
+                path: PathBuf::from("/etc/servics")
+            }
+            .to_string(),
+            "file \"/etc/servics\" does not exist"
+        );
+        assert_eq!(
+            ParseError::NotARegularFile {
+                path: PathBuf::from("/etc")
+            }
+            .to_string(),
+            "\"/etc\" is not a regular file"
+        );
+        assert_eq!(
+            ParseError::InvalidField {
+                field: "alias",
+                value: "world wide web".to_string()
+            }
+            .to_string(),
+            "invalid alias \"world wide web\""
+        );
+    }
+}
@@ -0,0 +1,636 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    comment_start, is_comment, parse_entry, strip_line_ending, validate, Conflict, EntryProblem,
+    ParseError, ParseErrorAt, ParseOptions, ParseStats, Parser, ReservedRange, ServiceDatabase,
+    ServiceEntry,
+};
+
+// Maximum nesting of `include` directives, see `ParseOptions::follow_includes`
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+// Roughly the number of bytes per entry of /etc/services, counting comment lines, used to size
+// the entries of a file before parsing it
+const BYTES_PER_ENTRY: u64 = 48;
+
+// The most entries reserved up front for a file, so that a huge file can't allocate much more
+// memory than the entries it actually holds before they're parsed
+const MAX_RESERVED_ENTRIES: usize = 1 << 16;
+
+// The number of entries to reserve for a file of `len` bytes, at most `opts.max_total_entries`
+pub(crate) fn reserve_hint(len: u64, opts: &ParseOptions) -> usize {
+    let hint = (len / BYTES_PER_ENTRY).min(MAX_RESERVED_ENTRIES as u64) as usize;
+    opts.max_total_entries.map_or(hint, |max| hint.min(max))
+}
+
+impl<'a, F: FnMut(usize)> Parser<'a, F> {
+    pub(crate) fn parse_file(&mut self, path: &Path) -> Result<(), ParseError> {
+        let file = open_file(path, self.opts)?;
+        let len = file.metadata()?.len();
+        self.parse_opened_file(path, BufReader::new(file), len)
+    }
+
+    // Parses the file at `path` which has already been opened as `reader`, and is `len` bytes
+    // long, e.g. after peeking at its first bytes
+    pub(crate) fn parse_opened_file<R: BufRead>(
+        &mut self,
+        path: &Path,
+        reader: R,
+        len: u64,
+    ) -> Result<(), ParseError> {
+        if self.keep_entries {
+            self.entries.reserve(reserve_hint(len, self.opts));
+        }
+        if !self.opts.follow_includes {
+            return self.parse_reader(reader);
+        }
+
+        let path = fs::canonicalize(path)?;
+        if self.include_stack.contains(&path) {
+            return Err(ParseError::IncludeCycle { path });
+        }
+        if self.include_stack.len() >= MAX_INCLUDE_DEPTH {
+            return Err(ParseError::IncludeDepthExceeded { path });
+        }
+
+        self.files.push(path.clone());
+        self.include_stack.push(path);
+        let result = self.parse_reader(reader);
+        self.include_stack.pop();
+        result
+    }
+
+    // Relative paths are resolved against the directory of the including file
+    pub(crate) fn include(&mut self, target: &str) -> Result<(), ParseError> {
+        let mut path = PathBuf::from(target);
+        if path.is_relative() {
+            if let Some(dir) = self.include_stack.last().and_then(|file| file.parent()) {
+                path = dir.join(path);
+            }
+        }
+
+        self.parse_file(&path)
+    }
+
+    pub(crate) fn parse_reader<R: BufRead>(&mut self, reader: R) -> Result<(), ParseError> {
+        let mut lines = LineReader::new(reader);
+        while lines.parse_next(self)? {}
+        Ok(())
+    }
+}
+
+// Feeds the lines of a reader to a `Parser` one at a time
+struct LineReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    // byte offset of the start of the next line
+    offset: usize,
+    line_number: usize,
+}
+
+impl<R: BufRead> LineReader<R> {
+    fn new(reader: R) -> Self {
+        LineReader {
+            reader,
+            buf: Vec::new(),
+            offset: 0,
+            line_number: 0,
+        }
+    }
+
+    // Parses the next line, returning false at the end of the input
+    fn parse_next<F: FnMut(usize)>(&mut self, parser: &mut Parser<F>) -> Result<bool, ParseError> {
+        self.buf.clear();
+        let len = self.reader.read_until(b'\n', &mut self.buf)?;
+        if len == 0 {
+            parser.finish_lines()?;
+            return Ok(false);
+        }
+        let line_offset = self.offset;
+        self.offset += len;
+        self.line_number += 1;
+
+        #[cfg(feature = "encoding")]
+        {
+            let encoding = parser.opts.encoding;
+            if encoding != encoding_rs::UTF_8 {
+                let (line, _) = encoding.decode_without_bom_handling(&self.buf);
+                parser.feed_line(strip_line_ending(&line), self.line_number, line_offset)?;
+                return Ok(true);
+            }
+        }
+
+        // A line which isn't valid UTF-8 is skipped like any other bad line
+        match std::str::from_utf8(&self.buf) {
+            Ok(line) => parser.feed_line(strip_line_ending(line), self.line_number, line_offset)?,
+            Err(_) => {
+                parser.count_line();
+                let err = io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                );
+                parser.bad_line(self.line_number, err.into())?;
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// An iterator over the entries of a reader, parsed as they're needed, see `parse_reader_iter`
+pub struct ServiceEntries<'a, R> {
+    parser: Parser<'a, fn(usize)>,
+    lines: LineReader<R>,
+    // entries parsed from the last line which haven't been returned yet
+    pending: std::vec::IntoIter<ServiceEntry>,
+    done: bool,
+}
+
+impl<'a, R: BufRead> Iterator for ServiceEntries<'a, R> {
+    type Item = Result<ServiceEntry, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.pending.next() {
+                return Some(Ok(entry));
+            }
+            if self.done {
+                return None;
+            }
+
+            match self.lines.parse_next(&mut self.parser) {
+                Ok(more) => {
+                    self.pending = std::mem::take(&mut self.parser.entries).into_iter();
+                    // entries which have been returned can't be merged into
+                    self.parser.definitions.clear();
+                    self.done = !more;
+                }
+                // reading can't continue after an I/O error, or once there are too many entries
+                Err(err @ ParseError::Io(_)) | Err(err @ ParseError::TooManyEntries(_)) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Parse services from a reader like `parse_reader`, but one line at a time as the entries are
+/// iterated over
+/// A bad line is returned as an error, or skipped if `opts.ignore_errs` is set, and iteration
+/// carries on with the next line. Entries are returned as soon as they're read, so
+/// `opts.merge_duplicate_definitions` has no effect.
+pub fn parse_reader_iter<R: BufRead>(reader: R, opts: &ParseOptions) -> ServiceEntries<'_, R> {
+    ServiceEntries {
+        parser: Parser::new(opts, |_| {}),
+        lines: LineReader::new(reader),
+        pending: Vec::new().into_iter(),
+        done: false,
+    }
+}
+
+// Symlinks are followed, so a dangling symlink is reported the same way as a missing file
+pub(crate) fn open_file(path: &Path, opts: &ParseOptions) -> Result<File, ParseError> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(ParseError::FileNotFound {
+                path: path.to_path_buf(),
+            });
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if metadata.is_dir() || !(metadata.is_file() || opts.allow_non_regular) {
+        return Err(ParseError::NotARegularFile {
+            path: path.to_path_buf(),
+        });
+    }
+
+    Ok(File::open(path)?)
+}
+
+/// Parse a file using the format described in `man services(5)`
+/// if ignore_errs is true, then all parsing errors will be ignored. This is needed on some systems
+/// which don't entirely respect the format in services(5) and omit a service name
+/// `path` may be a symlink, in which case the file it points to is parsed
+pub fn parse_file(path: &Path, ignore_errs: bool) -> Result<Vec<ServiceEntry>, ParseError> {
+    let opts = ParseOptions {
+        ignore_errs,
+        ..Default::default()
+    };
+    parse_file_with_options(path, &opts)
+}
+
+/// Parse a file like `parse_file`, skipping lines which have no service name but failing on any
+/// other malformed line, see `ParseOptions::ignore_missing_names`
+pub fn parse_file_lenient_names(path: &Path) -> Result<Vec<ServiceEntry>, ParseError> {
+    let opts = ParseOptions {
+        ignore_missing_names: true,
+        ..Default::default()
+    };
+    parse_file_with_options(path, &opts)
+}
+
+// The line number and error of each line skipped by `parse_file_with_warnings`
+type Warnings = Vec<(usize, ParseError)>;
+
+/// Parse a file, skipping bad lines like `parse_file` with `ignore_errs`, but returning the line
+/// number and error of every skipped line along with the valid entries
+/// Only fails if the file can't be opened or read.
+pub fn parse_file_with_warnings(path: &Path) -> Result<(Vec<ServiceEntry>, Warnings), ParseError> {
+    let opts = ParseOptions::default();
+    let mut parser = Parser::new(&opts, |_| {});
+    parser.errors = Some(Vec::new());
+    parser.parse_file(path)?;
+
+    let warnings = parser
+        .errors
+        .take()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|ParseErrorAt { line, error }| (line, error))
+        .collect();
+    Ok((parser.finish().0, warnings))
+}
+
+/// Parse a file like `parse_file`, with the behavior controlled by `opts`
+/// Rows recognized by `opts.capture_reserved` are skipped, use `parse_file_with_reserved` to get
+/// them back
+pub fn parse_file_with_options(
+    path: &Path,
+    opts: &ParseOptions,
+) -> Result<Vec<ServiceEntry>, ParseError> {
+    parse_file_with_reserved(path, opts).map(|(entries, _)| entries)
+}
+
+/// Parse a file like `parse_file_with_options`, additionally returning the placeholder rows
+/// recognized when `opts.capture_reserved` is set
+pub fn parse_file_with_reserved(
+    path: &Path,
+    opts: &ParseOptions,
+) -> Result<(Vec<ServiceEntry>, Vec<ReservedRange>), ParseError> {
+    let mut parser = Parser::new(opts, |_| {});
+    parser.parse_file(path)?;
+    Ok(parser.finish())
+}
+
+/// Parse every regular file in the directory at `path`, e.g. `/etc/services.d`, returning the
+/// entries of all files in the order they were parsed
+/// With `opts.sort_dir_entries` the files are parsed in the order of their names. Subdirectories
+/// are skipped.
+pub fn parse_dir(path: &Path, opts: &ParseOptions) -> Result<Vec<ServiceEntry>, ParseError> {
+    let dir = match fs::read_dir(path) {
+        Ok(dir) => dir,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(ParseError::FileNotFound {
+                path: path.to_path_buf(),
+            });
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let mut files = Vec::new();
+    for dir_entry in dir {
+        let file = dir_entry?.path();
+        if file.is_file() {
+            files.push(file);
+        }
+    }
+    if opts.sort_dir_entries {
+        files.sort();
+    }
+
+    let mut parser = Parser::new(opts, |_| {});
+    for file in &files {
+        parser.parse_file(file)?;
+    }
+    Ok(parser.finish().0)
+}
+
+/// Check that a file is well-formed without keeping its entries in memory, e.g. to validate a
+/// very large file on a constrained machine
+/// Every error is collected, even with `opts.ignore_errs`, along with the line it was found on.
+/// An error which stops parsing, like `ParseError::TooManyEntries`, is collected last, see
+/// `ServicesReport::errors`.
+/// Includes are followed if `opts.follow_includes` is set, errors in an included file have line
+/// numbers within that file.
+pub fn validate_file_streaming(
+    path: &Path,
+    opts: &ParseOptions,
+) -> Result<ParseStats, Vec<ParseErrorAt>> {
+    let mut parser = Parser::new(opts, |_| {});
+    parser.keep_entries = false;
+    parser.errors = Some(Vec::new());
+
+    let result = parser.parse_file(path);
+    let mut errors = parser.errors.take().unwrap_or_default();
+    if let Err(error) = result {
+        // an error which stopped parsing, on line 0 if the file couldn't be opened
+        errors.push(ParseErrorAt {
+            line: parser.line_number,
+            error,
+        });
+    }
+
+    if errors.is_empty() {
+        Ok(parser.stats)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Everything found when checking a file with `report`
+#[derive(Debug, PartialEq)]
+pub struct ServicesReport {
+    pub stats: ParseStats,
+    /// The valid entries, in file order
+    pub entries: Vec<ServiceEntry>,
+    /// The problems `ServiceEntry::validate` found, with the index of the entry in `entries`
+    pub problems: Vec<(usize, EntryProblem)>,
+    /// The lines which were skipped because they couldn't be parsed, and why. The last error may
+    /// have stopped parsing instead, e.g. `ParseError::TooManyEntries` on the line which hit the
+    /// limit or an I/O error after the last line read, and is on line 0 if the file couldn't be
+    /// opened.
+    pub errors: Vec<ParseErrorAt>,
+    /// The conflicts between entries found by `validate`
+    pub conflicts: Vec<Conflict>,
+    /// The ambiguous aliases found by `ServiceDatabase::find_alias_collisions`, with the entries
+    /// involved
+    pub alias_collisions: Vec<(String, Vec<ServiceEntry>)>,
+    /// The names on more than one port found by `ServiceDatabase::find_name_port_anomalies`
+    pub name_port_anomalies: Vec<(String, Vec<u16>)>,
+    /// The entries whose names are longer than `ServiceDatabase::DEFAULT_MAX_NAME_LEN`
+    pub overlong_names: Vec<ServiceEntry>,
+}
+
+impl ServicesReport {
+    /// Whether the file had no bad lines and nothing was found wrong with its entries
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+            && self.errors.is_empty()
+            && self.conflicts.is_empty()
+            && self.alias_collisions.is_empty()
+            && self.name_port_anomalies.is_empty()
+            && self.overlong_names.is_empty()
+    }
+}
+
+/// Check a file like `validate_file_streaming`, but keep the entries and run every check of the
+/// entries and of the database they make up as well, e.g. for a `check` command
+pub fn report(path: &Path, opts: &ParseOptions) -> ServicesReport {
+    let mut parser = Parser::new(opts, |_| {});
+    parser.errors = Some(Vec::new());
+
+    let result = parser.parse_file(path);
+    let mut errors = parser.errors.take().unwrap_or_default();
+    if let Err(error) = result {
+        errors.push(ParseErrorAt {
+            line: parser.line_number,
+            error,
+        });
+    }
+
+    let stats = parser.stats.clone();
+    let entries = parser.finish().0;
+    let problems = entries
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, entry)| {
+            entry
+                .validate()
+                .into_iter()
+                .map(move |problem| (idx, problem))
+        })
+        .collect();
+    let conflicts = validate(&entries);
+
+    let mut db = ServiceDatabase::with_options(entries, opts);
+    let alias_collisions = db
+        .find_alias_collisions()
+        .into_iter()
+        .map(|(alias, entries)| (alias, entries.into_iter().cloned().collect()))
+        .collect();
+    let name_port_anomalies = db.find_name_port_anomalies();
+    let overlong_names = db
+        .find_overlong_names(ServiceDatabase::DEFAULT_MAX_NAME_LEN)
+        .into_iter()
+        .cloned()
+        .collect();
+    ServicesReport {
+        stats,
+        entries: std::mem::take(db.entries_mut()),
+        problems,
+        errors,
+        conflicts,
+        alias_collisions,
+        name_port_anomalies,
+        overlong_names,
+    }
+}
+
+/// Parse services from any reader using the format described in `man services(5)`
+/// Lines may end in `\n` or `\r\n`, e.g. for a file edited on Windows, and whitespace at the end
+/// of a line is ignored.
+pub fn parse_reader<R: BufRead>(
+    reader: R,
+    opts: &ParseOptions,
+) -> Result<Vec<ServiceEntry>, ParseError> {
+    parse_reader_with_progress(reader, opts, |_| {})
+}
+
+/// Parse services from a reader like `parse_reader`, calling `on_line` with the number of lines
+/// read so far every `opts.progress_interval` lines
+/// A `progress_interval` of 0 disables the callback
+pub fn parse_reader_with_progress<R: BufRead, F: FnMut(usize)>(
+    reader: R,
+    opts: &ParseOptions,
+    on_line: F,
+) -> Result<Vec<ServiceEntry>, ParseError> {
+    let mut parser = Parser::new(opts, on_line);
+    parser.parse_reader(reader)?;
+    Ok(parser.finish().0)
+}
+
+/// Parse only the lines after the first `already_seen` lines of `reader`, e.g. to pick up the
+/// entries appended to a file since it was last read
+/// Counting the lines read so far is up to the caller, e.g. with `ParseStats::lines`.
+pub fn parse_appended<R: BufRead>(
+    mut reader: R,
+    already_seen: usize,
+) -> Result<Vec<ServiceEntry>, ParseError> {
+    let mut buf = Vec::new();
+    for _ in 0..already_seen {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+    }
+
+    parse_reader(reader, &ParseOptions::default())
+}
+
+/// Return the first entry in `reader` matching `pred`, without reading any further
+/// Lines which aren't valid entries are skipped. Pass `&mut reader` to carry on reading the lines
+/// after the match.
+pub fn find_in_reader<R: BufRead>(
+    mut reader: R,
+    pred: impl Fn(&ServiceEntry) -> bool,
+) -> io::Result<Option<ServiceEntry>> {
+    let opts = ParseOptions::default();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let entryline = line.trim_start();
+        if entryline.is_empty() || is_comment(entryline, &opts) {
+            continue;
+        }
+        if let Ok(entry) = parse_entry(entryline, &opts) {
+            if pred(&entry) {
+                return Ok(Some(entry));
+            }
+        }
+    }
+}
+
+/// Parse a file in the loose `name port protocol [aliases]` format used by some homegrown tools,
+/// where the port and protocol are separate columns
+/// Comments and blank lines are skipped as in a services file, and `port/protocol` is accepted
+/// too.
+pub fn parse_loose_file(path: &Path) -> Result<Vec<ServiceEntry>, ParseError> {
+    let opts = ParseOptions {
+        allow_space_before_protocol: true,
+        ..Default::default()
+    };
+    parse_file_with_options(path, &opts)
+}
+
+// Parses a line of a tab-separated file, see `parse_tsv`, returning `None` for a comment or blank
+// line
+fn parse_tsv_line(line: &str, opts: &ParseOptions) -> Result<Option<ServiceEntry>, ParseError> {
+    if line.trim().is_empty() || is_comment(line.trim_start(), opts) {
+        return Ok(None);
+    }
+
+    let mut columns = line.split('\t');
+    let name = columns.next().unwrap_or_default();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(ParseError::MalformedInput(name.to_string()));
+    }
+    let port_and_protocol = columns.next().unwrap_or_default();
+    if port_and_protocol.contains(char::is_whitespace) {
+        return Err(ParseError::MalformedPort(port_and_protocol.to_string()));
+    }
+    let mut entry = parse_entry(&format!("{} {}", name, port_and_protocol), opts)?;
+
+    if let Some(aliases) = columns.next() {
+        for alias in aliases.split_whitespace() {
+            match comment_start(alias, opts) {
+                // the rest of the line is a comment, whichever column it's in
+                Some(idx) => {
+                    entry.aliases.extend(
+                        Some(&alias[..idx])
+                            .filter(|a| !a.is_empty())
+                            .map(String::from),
+                    );
+                    return Ok(Some(entry));
+                }
+                None => entry.aliases.push(alias.to_string()),
+            }
+        }
+    }
+    if let Some(column) = columns.find(|column| !is_comment(column, opts)) {
+        return Err(ParseError::MalformedInput(column.to_string()));
+    }
+    Ok(Some(entry))
+}
+
+// Parses the lines of a tab-separated file, passing the line number and error of each bad line
+// to `bad_line`, which may skip it or stop parsing
+fn parse_tsv_lines<R: Read>(
+    reader: R,
+    mut bad_line: impl FnMut(usize, ParseError) -> Result<(), ParseError>,
+) -> Result<Vec<ServiceEntry>, ParseError> {
+    let opts = ParseOptions::default();
+    let mut entries = Vec::new();
+    for (idx, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        match parse_tsv_line(&line, &opts) {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => {}
+            Err(error) => bad_line(idx + 1, error)?,
+        }
+    }
+    Ok(entries)
+}
+
+/// Parse services from a strict tab-separated file with the columns name, port/protocol and
+/// aliases, where each tab ends a column, so `http\t80/tcp\t\t# web` has no aliases and a
+/// trailing comment column
+/// Aliases are separated by spaces within their column, and a comment may also start within it,
+/// e.g. `http\t80/tcp\twww # web`. Comment lines and blank lines are skipped, and a column after
+/// the aliases must be a comment. Fails with the error of the first bad line, see
+/// `parse_tsv_with_warnings` to skip them instead.
+pub fn parse_tsv<R: Read>(reader: R) -> Result<Vec<ServiceEntry>, ParseError> {
+    parse_tsv_lines(reader, |_, error| Err(error))
+}
+
+/// Parse a tab-separated file like `parse_tsv`, but skip bad lines like
+/// `parse_file_with_warnings`, returning the line number and error of every skipped line along
+/// with the valid entries
+/// Only fails if the reader can't be read.
+pub fn parse_tsv_with_warnings<R: Read>(
+    reader: R,
+) -> Result<(Vec<ServiceEntry>, Warnings), ParseError> {
+    let mut warnings = Vec::new();
+    let entries = parse_tsv_lines(reader, |line, error| {
+        warnings.push((line, error));
+        Ok(())
+    })?;
+    Ok((entries, warnings))
+}
+
+/// Parse services piped into the program's standard input, e.g. `cat custom.services | mytool`
+pub fn parse_stdin(opts: &ParseOptions) -> Result<Vec<ServiceEntry>, ParseError> {
+    parse_reader(io::stdin().lock(), opts)
+}
+
+/// Parse /etc/services
+pub fn parse_servicefile(ignore_errs: bool) -> Result<Vec<ServiceEntry>, ParseError> {
+    parse_file(Path::new("/etc/services"), ignore_errs)
+}
+
+/// Parse a file like `parse_file`, but treat a missing file as having no entries
+/// Other errors, e.g. `path` being a directory or containing malformed lines, are still returned
+pub fn parse_file_or_empty(
+    path: &Path,
+    ignore_errs: bool,
+) -> Result<Vec<ServiceEntry>, ParseError> {
+    match parse_file(path, ignore_errs) {
+        Err(ParseError::FileNotFound { .. }) => Ok(Vec::new()),
+        result => result,
+    }
+}
+
+/// Parse /etc/services, returning no entries if it does not exist (as on some container images)
+/// If the file has bad lines they're skipped even without `ignore_errs`, so that the valid entries
+/// aren't lost. Only a file which exists but can't be read at all gives no entries, use
+/// `parse_servicefile` to find out why.
+pub fn parse_servicefile_or_empty(ignore_errs: bool) -> Vec<ServiceEntry> {
+    parse_system_file_or_empty(Path::new("/etc/services"), ignore_errs)
+}
+
+// See `parse_servicefile_or_empty`
+pub(crate) fn parse_system_file_or_empty(path: &Path, ignore_errs: bool) -> Vec<ServiceEntry> {
+    match parse_file(path, ignore_errs) {
+        Ok(entries) => entries,
+        Err(ParseError::FileNotFound { .. }) => Vec::new(),
+        // a bad line, parse the file again skipping it
+        Err(_) if !ignore_errs => parse_file(path, true).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
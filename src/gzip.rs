@@ -0,0 +1,98 @@
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use flate2::bufread::GzDecoder;
+
+use crate::{open_file, parse_reader, ParseError, ParseOptions, Parser, ServiceEntry};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Parse a gzip-compressed services file, e.g. `/usr/share/iana-etc/services.gz`
+/// Relative `include` paths are resolved against the working directory.
+pub fn parse_gzip_file(path: &Path, opts: &ParseOptions) -> Result<Vec<ServiceEntry>, ParseError> {
+    let reader = BufReader::new(open_file(path, opts)?);
+    parse_reader(BufReader::new(GzDecoder::new(reader)), opts)
+}
+
+/// Parse a services file which may or may not be gzip-compressed, deciding by its first two
+/// bytes rather than its extension
+/// The file is only opened once, so this also works for a fifo with `opts.allow_non_regular`.
+pub fn parse_auto(path: &Path, opts: &ParseOptions) -> Result<Vec<ServiceEntry>, ParseError> {
+    let file = open_file(path, opts)?;
+    let len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        return parse_reader(BufReader::new(GzDecoder::new(reader)), opts);
+    }
+
+    // parsed like `parse_file_with_options`, from the bytes already read
+    let mut parser = Parser::new(opts, |_| {});
+    parser.parse_opened_file(path, reader, len)?;
+    Ok(parser.finish().0)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate mktemp;
+    use mktemp::Temp;
+
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::*;
+    use crate::parse_file_with_options;
+
+    const SERVICES: &str = "# services\ntcpmux 1/tcp\nhttp 80/tcp www www-http\n";
+
+    #[test]
+    fn gzip_and_plain() {
+        let temp_dir = Temp::new_dir().unwrap();
+        let plain = temp_dir.as_path().join("services");
+        // the extension deliberately doesn't match the contents
+        let compressed = temp_dir.as_path().join("services.txt");
+        fs::write(&plain, SERVICES).unwrap();
+        let mut encoder =
+            GzEncoder::new(File::create(&compressed).unwrap(), Compression::default());
+        encoder.write_all(SERVICES.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let opts = ParseOptions::default();
+        let expected = parse_file_with_options(&plain, &opts).unwrap();
+        assert_eq!(expected.len(), 2);
+        assert_eq!(parse_gzip_file(&compressed, &opts), Ok(expected.clone()));
+        assert_eq!(parse_auto(&compressed, &opts), Ok(expected.clone()));
+        assert_eq!(parse_auto(&plain, &opts), Ok(expected));
+
+        assert!(parse_gzip_file(&plain, &opts).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn plain_fifo() {
+        let temp_dir = Temp::new_dir().unwrap();
+        let fifo = temp_dir.as_path().join("services");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let writer = {
+            let fifo = fifo.clone();
+            std::thread::spawn(move || fs::write(fifo, SERVICES).unwrap())
+        };
+        let opts = ParseOptions {
+            allow_non_regular: true,
+            ..Default::default()
+        };
+        let entries = parse_auto(&fifo, &opts).unwrap();
+        writer.join().unwrap();
+        assert_eq!(
+            entries,
+            crate::parse_reader(SERVICES.as_bytes(), &opts).unwrap()
+        );
+    }
+}
@@ -1,9 +1,82 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::path::Path;
-use std::str::FromStr;
+// Without the `std` feature only parsing from strings is available, see `parse_str`
+#![cfg_attr(not(feature = "std"), no_std)]
 
-/**
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    borrow::Cow,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::ops::{Range, RangeInclusive};
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "std")]
+use std::net::{IpAddr, SocketAddr};
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+mod builder;
+#[cfg(feature = "cache")]
+mod cache;
+#[cfg(feature = "std")]
+mod database;
+#[cfg(feature = "std")]
+mod diff;
+mod error;
+#[cfg(feature = "std")]
+mod file;
+#[cfg(feature = "gzip")]
+mod gzip;
+#[cfg(all(feature = "nss", unix))]
+mod nss;
+mod protocol;
+#[cfg(feature = "std")]
+mod resolver;
+#[cfg(feature = "std")]
+mod service_file;
+#[cfg(feature = "std")]
+mod trie;
+mod validate;
+mod well_known;
+#[cfg(feature = "std")]
+mod writer;
+
+pub use builder::ServiceEntryBuilder;
+#[cfg(feature = "std")]
+pub use database::{DbEntry, LookupExplanation, MatchedVia, PortMismatch, ServiceDatabase};
+#[cfg(feature = "std")]
+pub use diff::{assert_equivalent, write_patch, ServicesDiff};
+#[cfg(feature = "encoding")]
+pub use encoding_rs;
+pub use error::{ParseError, ParseErrorAt};
+#[cfg(feature = "std")]
+pub use file::*;
+#[cfg(feature = "gzip")]
+pub use gzip::{parse_auto, parse_gzip_file};
+#[cfg(all(feature = "nss", unix))]
+pub use nss::{system_services_via_nss, NssResolver};
+pub use protocol::{Protocol, SocketService, Transport};
+#[cfg(feature = "std")]
+pub use resolver::ServiceResolver;
+#[cfg(feature = "std")]
+pub use service_file::{Line, ServiceFile};
+#[cfg(feature = "std")]
+pub use trie::NameTrie;
+pub use validate::{validate, Conflict, EntryProblem};
+pub use well_known::WellKnownService;
+#[cfg(feature = "std")]
+pub use writer::{Separator, ServiceFileWriter, WriteOptions};
+
+/*
  * service file format:
  *   File:
  *     Line |
@@ -29,141 +102,1154 @@ fn discard_ws(input: &str, start_idx: usize) -> usize {
             break;
         }
 
-        end_idx += 1;
+        end_idx += c.unwrap().len_utf8();
     }
 
     end_idx
 }
 
 /// A struct representing a line from /etc/services that has a service on it
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
 pub struct ServiceEntry {
     pub name: String,
-    pub port: usize,
+    pub port: u16,
     pub protocol: String,
     pub aliases: Vec<String>,
+    /// The ports covered by a `start-end/protocol` entry, only parsed when
+    /// `ParseOptions::allow_port_ranges` is enabled. `port` is the start of the range.
+    pub port_range: Option<RangeInclusive<u16>>,
+    /// Whether the entry was commented out, e.g. `#http 80/tcp`, only parsed when
+    /// `ParseOptions::parse_disabled` is enabled
+    pub disabled: bool,
+    /// The text after a `%` in the protocol field, e.g. `eth0` for `tcp%eth0`, only split off
+    /// when `ParseOptions::split_protocol_suffix` is enabled
+    pub protocol_suffix: Option<String>,
+    /// The protocol as it was written, e.g. `TCP` when `protocol` is `tcp`, before any synonym
+    /// was applied. Only set when `ParseOptions::preserve_original_protocol` is enabled.
+    pub protocol_raw: Option<String>,
+    /// The text of the trailing comment without the leading `#`, e.g. `World Wide Web HTTP`. Only
+    /// set when `ParseOptions::keep_comments` is enabled and the comment isn't empty.
+    pub comment: Option<String>,
+    /// The line the entry was parsed from, only set when `ParseOptions::keep_raw` is enabled
+    pub raw: Option<String>,
+    /// The byte offsets of the entry in the file it was parsed from, from the start of the service
+    /// name to the end of the line. Only set when `ParseOptions::track_spans` is enabled.
+    pub span: Option<Range<usize>>,
+}
+
+/// The IANA port number ranges, see RFC 6335
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PortClass {
+    /// 0-1023, also known as well-known ports
+    System,
+    /// 1024-49151, also known as user ports
+    Registered,
+    /// 49152-65535, also known as private or ephemeral ports
+    Dynamic,
+}
+
+impl PortClass {
+    pub fn of(port: u16) -> PortClass {
+        match port {
+            0..=1023 => PortClass::System,
+            1024..=49151 => PortClass::Registered,
+            _ => PortClass::Dynamic,
+        }
+    }
+}
+
+impl ServiceEntry {
+    /// The class of the entry's port, for a range this is the class of its first port
+    pub fn port_class(&self) -> PortClass {
+        PortClass::of(self.port)
+    }
+
+    /// The port/protocol field as written in a services file, e.g. `80/tcp`, or `8000-8010/tcp`
+    /// for an entry with a `port_range`
+    pub fn port_protocol(&self) -> String {
+        let field = match &self.port_range {
+            Some(range) => format!("{}-{}/{}", range.start(), range.end(), self.protocol),
+            None => format!("{}/{}", self.port, self.protocol),
+        };
+        match &self.protocol_suffix {
+            Some(suffix) => format!("{}%{}", field, suffix),
+            None => field,
+        }
+    }
+
+    /// A key identifying the service, e.g. for a map or cache: the lowercased name and protocol
+    /// as `name/protocol`, like `http/tcp`
+    /// The port and aliases aren't part of the key, so like `ServiceDatabase::diff` and
+    /// `ServiceDatabase::with_overrides` it treats entries for the same service on different
+    /// ports as the same service.
+    pub fn key(&self) -> String {
+        format!(
+            "{}/{}",
+            self.name.to_lowercase(),
+            self.protocol.to_lowercase()
+        )
+    }
+
+    /// The entry as a line in the layout of the IANA registry's services file, e.g. for tools
+    /// contributing to it: the name and the port/protocol field each take up 15 columns, followed
+    /// by the aliases and the comment
+    /// A field which doesn't fit is followed by a single space. A disabled entry is commented out.
+    pub fn to_iana_line(&self) -> String {
+        let mut line = format!(
+            "{:<14} {:<14} {}",
+            self.name,
+            self.port_protocol(),
+            self.aliases.join(" ")
+        );
+        line.truncate(line.trim_end().len());
+        if let Some(comment) = &self.comment {
+            line = format!("{} # {}", line, comment);
+        }
+        if self.disabled {
+            line.insert(0, '#');
+        }
+        line
+    }
+
+    /// The name with any whitespace replaced by `_`, so that it can be written as a single field
+    /// of a services file, e.g. for a name built in code
+    /// `ServiceFileWriter` refuses to write names containing whitespace instead.
+    pub fn escaped_name(&self) -> Cow<'_, str> {
+        if self.name.contains(char::is_whitespace) {
+            Cow::Owned(self.name.replace(char::is_whitespace, "_"))
+        } else {
+            Cow::Borrowed(&self.name)
+        }
+    }
+
+    /// The same entry on `port`, e.g. to derive an entry for an alternate port
+    /// Any `port_range` is removed, as are `raw` and `span` since they no longer describe the
+    /// entry.
+    pub fn with_port(self, port: u16) -> Self {
+        ServiceEntry {
+            port,
+            port_range: None,
+            raw: None,
+            span: None,
+            ..self
+        }
+    }
+
+    /// One entry for each port of the entry's `port_range`, see `with_port`, or just a copy of the
+    /// entry if it doesn't have a range, e.g. for tools which only understand single ports
+    pub fn expand_range(&self) -> Vec<ServiceEntry> {
+        match &self.port_range {
+            Some(range) => range
+                .clone()
+                .map(|port| self.clone().with_port(port))
+                .collect(),
+            None => vec![self.clone()],
+        }
+    }
+
+    /// The same entry for `protocol`, e.g. to derive the UDP twin of a TCP entry
+    /// `raw` and `span` are removed since they no longer describe the entry.
+    pub fn with_protocol(self, protocol: impl Into<String>) -> Self {
+        ServiceEntry {
+            protocol: protocol.into(),
+            raw: None,
+            span: None,
+            ..self
+        }
+    }
+
+    /// Compare two entries ignoring the case of the names and protocol, e.g. to compare files from
+    /// sources with inconsistent casing
+    /// Unlike `==` the comment, raw line and span aren't compared.
+    pub fn eq_normalized(&self, other: &ServiceEntry) -> bool {
+        let names_eq = |a: &str, b: &str| a.to_lowercase() == b.to_lowercase();
+        names_eq(&self.name, &other.name)
+            && self.port == other.port
+            && self.protocol.eq_ignore_ascii_case(&other.protocol)
+            && self.aliases.len() == other.aliases.len()
+            && self
+                .aliases
+                .iter()
+                .zip(&other.aliases)
+                .all(|(a, b)| names_eq(a, b))
+            && self.port_range == other.port_range
+            && self.protocol_suffix == other.protocol_suffix
+            && self.disabled == other.disabled
+    }
+
+    /// The aliases `other` has which this entry doesn't, and the ones this entry has which
+    /// `other` doesn't, e.g. to log how a service changed between two versions of a file
+    /// Each list is in the order of the entry it's from.
+    pub fn alias_diff<'a>(&'a self, other: &'a ServiceEntry) -> (Vec<&'a str>, Vec<&'a str>) {
+        let missing_from = |from: &'a ServiceEntry, entry: &ServiceEntry| -> Vec<&'a str> {
+            from.aliases
+                .iter()
+                .filter(|alias| !entry.aliases.contains(alias))
+                .map(String::as_str)
+                .collect()
+        };
+        (missing_from(other, self), missing_from(self, other))
+    }
+
+    /// Give the service a new canonical name, e.g. when refactoring a services file
+    /// If `keep_old_as_alias` is true the old name becomes an alias, so that clients using it can
+    /// still find the service. If the new name was an alias it's no longer listed as one.
+    pub fn rename(&mut self, new_name: impl Into<String>, keep_old_as_alias: bool) {
+        let old_name = core::mem::replace(&mut self.name, new_name.into());
+        let name = &self.name;
+        self.aliases.retain(|alias| alias != name);
+        if keep_old_as_alias && old_name != self.name && !self.aliases.contains(&old_name) {
+            self.aliases.push(old_name);
+        }
+    }
+
+    /// Remove the aliases equal to `alias` ignoring case, keeping the order of the others
+    /// Returns whether any were removed.
+    pub fn remove_alias(&mut self, alias: &str) -> bool {
+        let alias = alias.to_lowercase();
+        let len = self.aliases.len();
+        self.aliases.retain(|other| other.to_lowercase() != alias);
+        self.aliases.len() != len
+    }
+
+    /// The address of this service on a host which has already been resolved to `ip`
+    #[cfg(feature = "std")]
+    pub fn socket_addr(&self, ip: IpAddr) -> SocketAddr {
+        SocketAddr::new(ip, self.port)
+    }
+
+    /// Parse `key=value` pairs from the entry's comment, e.g. `# owner=team-x tier=backend`
+    /// Any other text in the comment is ignored.
+    #[cfg(feature = "std")]
+    pub fn comment_tags(&self) -> HashMap<String, String> {
+        let comment = self.comment.as_deref().unwrap_or("");
+        comment
+            .split_whitespace()
+            .filter_map(tag)
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// The entry's comment without any `key=value` tags, e.g. `My service` for
+    /// `# My service owner=team-x`, or None if there's no text left
+    /// Tags are expected before or after the text, a tag between two words of text is kept.
+    pub fn description(&self) -> Option<&str> {
+        let comment = self.comment.as_deref()?;
+        let mut text = comment
+            .split_whitespace()
+            .filter(|word| tag(word).is_none());
+        let first = text.next()?;
+        let last = text.next_back().unwrap_or(first);
+
+        // words are slices of `comment`
+        let start = first.as_ptr() as usize - comment.as_ptr() as usize;
+        let end = last.as_ptr() as usize - comment.as_ptr() as usize + last.len();
+        Some(&comment[start..end])
+    }
+
+    /// Lowercase and trim the protocol so that entries from different sources compare equal, e.g.
+    /// `TCP`, `Tcp` and `tcp`. The name and aliases are trimmed, and lowercased as well if
+    /// `lowercase_name` is true.
+    pub fn normalize(&mut self, lowercase_name: bool) {
+        self.protocol = self.protocol.trim().to_lowercase();
+
+        let names = core::iter::once(&mut self.name).chain(self.aliases.iter_mut());
+        for name in names {
+            *name = if lowercase_name {
+                name.trim().to_lowercase()
+            } else {
+                name.trim().to_string()
+            };
+        }
+    }
+}
+
+// Splits a `key=value` word of a comment, see `ServiceEntry::comment_tags`
+fn tag(word: &str) -> Option<(&str, &str)> {
+    word.split_once('=').filter(|(key, _)| !key.is_empty())
+}
+
+/// How the case of a parsed field is normalized, see `ParseOptions::protocol_case`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Case {
+    Lower,
+    Upper,
+    /// Keep the field as written
+    Preserve,
 }
 
-fn is_comment(s: &str) -> bool {
+impl Case {
+    fn apply(self, s: &str) -> String {
+        match self {
+            Case::Lower => s.to_lowercase(),
+            Case::Upper => s.to_uppercase(),
+            Case::Preserve => s.to_string(),
+        }
+    }
+}
+
+/// The order of the name and port/protocol fields of an entry, see `ParseOptions::field_order`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldOrder {
+    /// `http 80/tcp www`, as in /etc/services
+    NameFirst,
+    /// `80/tcp http www` or `80 http tcp www`, as exported by some tools
+    PortFirst,
+}
+
+/// Which entry a `ServiceDatabase` lookup returns when several entries match, e.g. in a file
+/// aggregated from several sources, see `ParseOptions::lookup_policy`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
+pub enum LookupPolicy {
+    /// The entry which was added first
+    #[default]
+    FirstWins,
+    /// The entry which was added last, e.g. so that later files override earlier ones
+    LastWins,
+}
+
+/// Options controlling how a services file is parsed
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Ignore all parsing errors, see `parse_file`
+    pub ignore_errs: bool,
+    /// Skip lines without a service name, see `ParseError::MissingServiceName`, but still fail on
+    /// any other error
+    pub ignore_missing_names: bool,
+    /// Recognize the IANA placeholder rows which have no service name and a description of
+    /// "Unassigned" or "Reserved" (e.g. `   0/tcp    Reserved`). These rows are returned
+    /// separately by `parse_file_with_reserved` instead of being reported as errors.
+    pub capture_reserved: bool,
+    /// Treat `;` as starting a comment in addition to `#`, both for whole lines and after the
+    /// port/protocol field
+    pub also_treat_semicolon_as_comment: bool,
+    /// Remove a `;` ending the fields of a line, e.g. `http 80/tcp; # web` from generators which
+    /// terminate entries like C statements. Otherwise the `;` is read as part of the last field.
+    pub strip_trailing_semicolon: bool,
+    /// How many lines `parse_reader_with_progress` reads between calls to its callback
+    pub progress_interval: usize,
+    /// Keep the original line each entry was parsed from in `ServiceEntry::raw`, so that
+    /// unmodified entries can be written back byte-for-byte
+    pub keep_raw: bool,
+    /// Treat lines of the form `include <path>` as a directive to parse the entries of another
+    /// file in place. Relative paths are resolved against the directory of the including file
+    /// (or the working directory when parsing a reader). Include cycles and nesting deeper than
+    /// 16 files are reported as errors. A line like `include 1234/tcp`, where the path would be a
+    /// port and protocol, is still parsed as an entry.
+    pub follow_includes: bool,
+    /// Accept the nonstandard `start-end/protocol` notation for a range of ports, see
+    /// `ServiceEntry::port_range`
+    pub allow_port_ranges: bool,
+    /// Keep aliases such as `foo#bar` verbatim. By default a comment may start in the middle of
+    /// an alias, like with glibc, so `foo#bar` is read as the alias `foo` followed by a comment.
+    /// A `#` glued to the protocol, as in `80/tcp#web`, starts a comment either way.
+    pub literal_hash_in_aliases: bool,
+    /// Accept a port and protocol separated by whitespace instead of a slash, so that `foo 80 tcp`
+    /// is read as `foo 80/tcp`. Only applies when the port field has no slash.
+    pub allow_space_before_protocol: bool,
+    /// Record where each entry was found in `ServiceEntry::span`, e.g. for editor integrations
+    pub track_spans: bool,
+    /// Index the lowercased names and aliases of a `ServiceDatabase` built with
+    /// `ServiceDatabase::with_options`. Without the index name and alias lookups scan every entry,
+    /// which saves memory but is much slower for large files.
+    pub build_case_folded_index: bool,
+    /// Keep the comment at the end of each entry's line in `ServiceEntry::comment`
+    pub keep_comments: bool,
+    /// How protocols are stored, lowercase by default so that `TCP` and `tcp` entries are
+    /// consistent
+    pub protocol_case: Case,
+    /// Keep the protocol as it was written in `ServiceEntry::protocol_raw`, e.g. to write it back
+    /// faithfully, and always lowercase `ServiceEntry::protocol` for lookups, whatever
+    /// `protocol_case` is
+    pub preserve_original_protocol: bool,
+    /// Lowercase service names and aliases, e.g. so that `HTTP 80/tcp WWW` is read as
+    /// `http 80/tcp www`, for sources with inconsistent capitalization
+    pub lowercase_names: bool,
+    /// Merge an entry into an earlier one with the same name, protocol and port, adding any new
+    /// aliases to the earlier entry, e.g. `http 80/tcp www` and `http 80/tcp www-http` become
+    /// `http 80/tcp www www-http`. Entries with the same name and protocol but a different port
+    /// are still kept separately.
+    pub merge_duplicate_definitions: bool,
+    /// Read the port/protocol field as `protocol/port`, e.g. `http tcp/80`, as emitted by some
+    /// tools. A field where the protocol is numeric too, like `80/tcp`, is rejected.
+    pub proto_port_order: bool,
+    /// Whether entries start with the service name or the port/protocol. With `PortFirst` a port
+    /// without a protocol is followed by the name and then the protocol, e.g. `80 http tcp`.
+    pub field_order: FieldOrder,
+    /// Protocols to replace with a canonical name while parsing, e.g. `tcp6` -> `tcp`, so that
+    /// lookups and diffs treat entries from different sources alike. Protocols are matched
+    /// ignoring case, and the replacement is still subject to `protocol_case`.
+    pub protocol_synonyms: BTreeMap<String, String>,
+    /// Accept a comma-separated list of protocols, e.g. `myproto 9000/tcp,udp`, producing one
+    /// entry per protocol with the same name, port and aliases. Otherwise `tcp,udp` is kept as
+    /// the protocol.
+    pub allow_comma_protocols: bool,
+    /// Split a protocol such as `tcp%eth0` into the protocol `tcp` and the
+    /// `ServiceEntry::protocol_suffix` `eth0`, instead of keeping `tcp%eth0` as the protocol
+    pub split_protocol_suffix: bool,
+    /// Which entry lookups on a `ServiceDatabase` built with `ServiceDatabase::with_options`
+    /// return when several entries match
+    pub lookup_policy: LookupPolicy,
+    /// Parse comment lines which hold an entry, e.g. `#http 80/tcp`, as entries with
+    /// `ServiceEntry::disabled` set, instead of skipping them. Comment lines which aren't entries
+    /// are still skipped.
+    pub parse_disabled: bool,
+    /// Read files which aren't regular files, such as named pipes or devices, instead of returning
+    /// `ParseError::NotARegularFile`, e.g. in a container where /etc/services is piped in.
+    /// Directories are still rejected. Reading a named pipe blocks until it has a writer, and until
+    /// the writer closes it, so this can hang forever on a misconfigured system.
+    pub allow_non_regular: bool,
+    /// Join a line ending in `\` with the line after it before parsing, e.g. for generated files
+    /// which wrap long comments or alias lists. The backslash and line break are replaced with a
+    /// space, so `http 80/tcp\` followed by `www` is read as `http 80/tcp www`. Errors are reported
+    /// at the first of the joined lines, the raw line of an entry is the joined line, and its span
+    /// covers all of the lines it was joined from.
+    pub line_continuations: bool,
+    /// Parse the files of a directory in `parse_dir` in the lexicographic order of their names,
+    /// rather than the unspecified order the filesystem lists them in. This decides which of
+    /// several entries found in different files wins a lookup, see `lookup_policy`.
+    pub sort_dir_entries: bool,
+    /// Read lines of the form `+name alias...` as adding aliases to the entries called `name`
+    /// parsed so far, e.g. in an overlay file parsed after the base file with `parse_dir` or an
+    /// include. A name with no earlier entry is an error. Entries already returned by
+    /// `parse_reader_iter` can't be merged into.
+    pub allow_merge_markers: bool,
+    /// Stop parsing with `ParseError::TooManyEntries` once more than this many entries have been
+    /// read, even with `ignore_errs`, to bound the memory used by untrusted input. Entries split
+    /// by `allow_comma_protocols` count separately.
+    pub max_total_entries: Option<usize>,
+    /// The encoding of files and readers, e.g. `encoding_rs::WINDOWS_1252` for a legacy Latin-1
+    /// file. Lines are decoded one at a time, so the encoding must be ASCII-compatible (UTF-16 is
+    /// not supported). Spans are offsets into the decoded lines rather than the raw bytes.
+    #[cfg(feature = "encoding")]
+    pub encoding: &'static encoding_rs::Encoding,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            ignore_errs: false,
+            ignore_missing_names: false,
+            capture_reserved: false,
+            also_treat_semicolon_as_comment: false,
+            strip_trailing_semicolon: false,
+            progress_interval: 1000,
+            keep_raw: false,
+            follow_includes: false,
+            allow_port_ranges: false,
+            literal_hash_in_aliases: false,
+            allow_space_before_protocol: false,
+            track_spans: false,
+            build_case_folded_index: true,
+            keep_comments: false,
+            protocol_case: Case::Lower,
+            preserve_original_protocol: false,
+            lowercase_names: false,
+            merge_duplicate_definitions: false,
+            proto_port_order: false,
+            field_order: FieldOrder::NameFirst,
+            protocol_synonyms: BTreeMap::new(),
+            allow_comma_protocols: false,
+            split_protocol_suffix: false,
+            lookup_policy: LookupPolicy::FirstWins,
+            parse_disabled: false,
+            allow_non_regular: false,
+            line_continuations: false,
+            sort_dir_entries: true,
+            allow_merge_markers: false,
+            max_total_entries: None,
+            #[cfg(feature = "encoding")]
+            encoding: encoding_rs::UTF_8,
+        }
+    }
+}
+
+fn is_comment(s: &str, opts: &ParseOptions) -> bool {
     if let Some(c) = s.chars().next() {
-        return c == '#';
+        return c == '#' || (opts.also_treat_semicolon_as_comment && c == ';');
     }
 
     false
 }
 
-impl FromStr for ServiceEntry {
-    type Err = &'static str;
+/// Check whether `line` of a services file is a comment, ignoring leading whitespace, the same
+/// way the parse functions do with the default options
+pub fn line_is_comment(line: &str) -> bool {
+    is_comment(&line[discard_ws(line, 0)..], &ParseOptions::default())
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut service = s.split_whitespace();
+// Finds where a comment starts within a token, e.g. the `#` in `foo#bar`
+fn comment_start(s: &str, opts: &ParseOptions) -> Option<usize> {
+    s.find(|c| c == '#' || (opts.also_treat_semicolon_as_comment && c == ';'))
+}
 
-        let name = service.next();
-        let name = name.unwrap().to_string();
-        if is_comment(&name) {
-            return Err("Malformed input");
+// Parses a port, or a `start-end` range of ports if `opts.allow_port_ranges` is set
+fn parse_port(
+    port: &str,
+    opts: &ParseOptions,
+) -> Result<(u16, Option<RangeInclusive<u16>>), ParseError> {
+    let malformed = || ParseError::MalformedPort(port.to_string());
+
+    if opts.allow_port_ranges {
+        if let Some((start, end)) = port.split_once('-') {
+            let start = start.parse::<u16>().map_err(|_| malformed())?;
+            let end = end.parse::<u16>().map_err(|_| malformed())?;
+            if start > end {
+                return Err(malformed());
+            }
+            return Ok((start, Some(start..=end)));
         }
+    }
+
+    let port = port.parse::<u16>().map_err(|_| malformed())?;
+    Ok((port, None))
+}
+
+// Whether `field` is a `port/protocol` field, or `protocol/port` with `opts.proto_port_order`
+#[cfg(feature = "std")]
+fn is_port_protocol(field: &str, opts: &ParseOptions) -> bool {
+    match field.split_once('/') {
+        Some((_, port)) if opts.proto_port_order => parse_port(port, opts).is_ok(),
+        Some((port, _)) => parse_port(port, opts).is_ok(),
+        None => false,
+    }
+}
+
+// Applies `protocol_synonyms` and `protocol_case`
+fn canonical_protocol(protocol: &str, opts: &ParseOptions) -> String {
+    let protocol = opts
+        .protocol_synonyms
+        .iter()
+        .find(|(synonym, _)| synonym.eq_ignore_ascii_case(protocol))
+        .map_or(protocol, |(_, canonical)| canonical);
+    if opts.preserve_original_protocol {
+        return protocol.to_lowercase();
+    }
+    opts.protocol_case.apply(protocol)
+}
+
+// Applies `lowercase_names` to a name or alias
+fn fold_name(name: &str, opts: &ParseOptions) -> String {
+    if opts.lowercase_names {
+        name.to_lowercase()
+    } else {
+        name.to_string()
+    }
+}
 
-        let port_and_protocol = service.next();
-        if port_and_protocol.is_none() {
-            return Err("Could not find port and protocol field");
+fn parse_entry(s: &str, opts: &ParseOptions) -> Result<ServiceEntry, ParseError> {
+    parse_fields(s, opts, true)
+}
+
+/// Parse a single line like `ServiceEntry::from_str`, but stop after the protocol
+/// `aliases` is always empty, which saves allocating the aliases of callers which only need the
+/// name, port and protocol.
+pub fn parse_line_no_aliases(line: &str) -> Result<ServiceEntry, ParseError> {
+    parse_fields(line, &ParseOptions::default(), false)
+}
+
+// Removes a `;` ending the fields of `s`, before any trailing comment. Like in `parse_fields`, a
+// `#` inside the name doesn't start a comment, and one inside an alias only does without
+// `opts.literal_hash_in_aliases`.
+fn strip_trailing_semicolon<'a>(s: &'a str, opts: &ParseOptions) -> Cow<'a, str> {
+    let comment_idx = s
+        .split_whitespace()
+        .enumerate()
+        .skip(1)
+        .find_map(|(i, field)| {
+            let idx = if is_comment(field, opts) {
+                Some(0)
+            } else {
+                comment_start(field, opts).filter(|_| i == 1 || !opts.literal_hash_in_aliases)
+            };
+            // fields are slices of `s`
+            idx.map(|idx| field.as_ptr() as usize - s.as_ptr() as usize + idx)
+        })
+        .unwrap_or(s.len());
+
+    let (fields, comment) = s.split_at(comment_idx);
+    match fields.trim_end().strip_suffix(';') {
+        Some(fields) => Cow::Owned(format!("{} {}", fields, comment)),
+        None => Cow::Borrowed(s),
+    }
+}
+
+fn parse_fields(
+    s: &str,
+    opts: &ParseOptions,
+    with_aliases: bool,
+) -> Result<ServiceEntry, ParseError> {
+    let s = if opts.strip_trailing_semicolon {
+        strip_trailing_semicolon(s, opts)
+    } else {
+        Cow::Borrowed(s)
+    };
+    let s = &*s;
+    let mut service = s.split_whitespace();
+
+    let (name, port_and_protocol) = match opts.field_order {
+        FieldOrder::NameFirst => (service.next(), service.next()),
+        FieldOrder::PortFirst => {
+            let port_and_protocol = service.next();
+            match service.next() {
+                Some(name) => (Some(name), port_and_protocol),
+                None => return Err(ParseError::MalformedInput(s.trim().to_string())),
+            }
+        }
+    };
+    // an empty line has no name
+    let name = fold_name(name.unwrap_or_default(), opts);
+    if name.is_empty() || is_comment(&name, opts) {
+        return Err(ParseError::MalformedInput(name));
+    }
+    // e.g. `   80/tcp`, where the name was left out
+    if let Some((port, _)) = name.split_once('/') {
+        if opts.field_order == FieldOrder::NameFirst && parse_port(port, opts).is_ok() {
+            return Err(ParseError::MissingServiceName(name));
         }
-        let mut port_and_protocol = port_and_protocol.unwrap().split("/");
+    }
+
+    if port_and_protocol.is_none() {
+        return Err(ParseError::MissingPortProtocol(name));
+    }
+    let field = port_and_protocol.unwrap();
+    let mut port_and_protocol = field.split('/');
 
-        let port = port_and_protocol.next().unwrap();
-        if is_comment(port) {
-            return Err("Could not find port and protocol field");
+    let mut port_str = port_and_protocol.next().unwrap();
+    if is_comment(port_str, opts) {
+        return Err(ParseError::MissingPortProtocol(name));
+    }
+    let mut protocol = port_and_protocol.next();
+    if let (true, Some(port)) = (opts.proto_port_order, protocol) {
+        // only one side may be a port, so that e.g. `80/tcp` isn't taken for a protocol of `80`
+        if parse_port(port_str, opts).is_ok() {
+            return Err(ParseError::MalformedPort(field.to_string()));
         }
-        let port = port.parse::<usize>();
-        if port.is_err() {
-            return Err("Malformed port");
+        protocol = Some(port_str);
+        port_str = port;
+    }
+    let (port, port_range) = parse_port(port_str, opts)?;
+
+    let protocol = match protocol {
+        Some(protocol) => protocol,
+        // e.g. `foo 80 tcp`, where the slash was left out
+        None if opts.allow_space_before_protocol || opts.field_order == FieldOrder::PortFirst => {
+            match service.next() {
+                Some(protocol) if !is_comment(protocol, opts) => protocol,
+                _ => return Err(ParseError::MissingProtocol(port_str.to_string())),
+            }
         }
-        let port = port.unwrap();
+        None => return Err(ParseError::MissingProtocol(port_str.to_string())),
+    };
 
-        let protocol = port_and_protocol.next();
-        if protocol.is_none() {
-            return Err("Could not find protocol");
+    let mut aliases = Vec::new();
+    // byte offset in `s` of the character starting the trailing comment
+    let mut comment_idx = None;
+    // A comment glued to the protocol, e.g. `80/tcp#web`, ends the entry. Unlike aliases,
+    // `literal_hash_in_aliases` doesn't apply since a protocol can't contain a `#`.
+    let protocol = match comment_start(protocol, opts) {
+        Some(idx) => {
+            comment_idx = Some(protocol.as_ptr() as usize - s.as_ptr() as usize + idx);
+            &protocol[..idx]
         }
-        let protocol = protocol.unwrap().to_string();
-        if is_comment(&protocol) {
-            return Err("Could not find protocol");
+        None => protocol,
+    };
+    let (protocol, protocol_suffix) = match protocol.split_once('%') {
+        Some((protocol, suffix)) if opts.split_protocol_suffix => (
+            protocol,
+            Some(suffix.to_string()).filter(|suffix| !suffix.is_empty()),
+        ),
+        _ => (protocol, None),
+    };
+    let protocol_raw = Some(protocol.to_string()).filter(|_| opts.preserve_original_protocol);
+    let protocol = if opts.allow_comma_protocols {
+        let protocols: Vec<_> = protocol
+            .split(',')
+            .map(|protocol| canonical_protocol(protocol, opts))
+            .collect();
+        if protocols.iter().any(String::is_empty) {
+            return Err(ParseError::MissingProtocol(format!("{}/", port_str)));
         }
+        protocols.join(",")
+    } else {
+        canonical_protocol(protocol, opts)
+    };
+    if protocol.is_empty() || is_comment(&protocol, opts) {
+        return Err(ParseError::MissingProtocol(format!("{}/", port_str)));
+    }
 
-        let mut aliases = Vec::new();
-        for alias in service {
-            if let Some(c) = alias.chars().next() {
-                if c == '#' {
-                    break;
-                }
+    let aliases_commented_out = comment_idx.is_some() || !with_aliases;
+    for alias in service.take_while(|_| !aliases_commented_out) {
+        // tokens are slices of `s`
+        let alias_idx = alias.as_ptr() as usize - s.as_ptr() as usize;
+        if is_comment(alias, opts) {
+            comment_idx = Some(alias_idx);
+            break;
+        }
+
+        match comment_start(alias, opts) {
+            Some(idx) if !opts.literal_hash_in_aliases => {
+                aliases.push(fold_name(&alias[..idx], opts));
+                comment_idx = Some(alias_idx + idx);
+                break;
             }
+            _ => aliases.push(fold_name(alias, opts)),
+        }
+    }
 
-            aliases.push(alias.to_string());
+    let comment = match comment_idx {
+        Some(idx) if opts.keep_comments => {
+            let comment = s[idx + 1..].trim();
+            Some(comment.to_string()).filter(|comment| !comment.is_empty())
         }
+        _ => None,
+    };
 
-        Ok(ServiceEntry {
-            name,
-            port,
-            protocol,
-            aliases,
-        })
+    Ok(ServiceEntry {
+        name,
+        port,
+        protocol,
+        aliases,
+        port_range,
+        protocol_suffix,
+        protocol_raw,
+        disabled: false,
+        comment,
+        raw: None,
+        span: None,
+    })
+}
+
+impl FromStr for ServiceEntry {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_entry(s, &ParseOptions::default())
+    }
+}
+
+/// Compare an entry to a line of a services file, e.g. `assert_eq!(entry, "http 80/tcp www")`
+/// The line is parsed with the default options, so whitespace and trailing comments don't matter.
+/// The name, port, protocol, aliases and port range are compared, and a line which can't be
+/// parsed is never equal.
+impl PartialEq<str> for ServiceEntry {
+    fn eq(&self, line: &str) -> bool {
+        match line.parse::<ServiceEntry>() {
+            Ok(other) => {
+                self.name == other.name
+                    && self.port == other.port
+                    && self.protocol == other.protocol
+                    && self.aliases == other.aliases
+                    && self.port_range == other.port_range
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+impl PartialEq<&str> for ServiceEntry {
+    fn eq(&self, line: &&str) -> bool {
+        self == *line
     }
 }
 
-/// Parse a file using the format described in `man services(5)`
-/// if ignore_errs is true, then all parsing errors will be ignored. This is needed on some systems
-/// which don't entirely respect the format in services(5) and omit a service name
-pub fn parse_file(path: &Path, ignore_errs: bool) -> Result<Vec<ServiceEntry>, &'static str> {
-    if !path.exists() || !path.is_file() {
-        return Err("File does not exist or is not a regular file");
+/// A port that the IANA registry explicitly marks as "Unassigned" or "Reserved"
+#[derive(Debug, PartialEq)]
+pub struct ReservedRange {
+    pub port: u16,
+    pub protocol: String,
+}
+
+// Recognizes a placeholder row from the IANA registry. These rows start with the port/protocol
+// field because the service name is blank.
+fn parse_reserved(s: &str, opts: &ParseOptions) -> Option<ReservedRange> {
+    let mut fields = s.split_whitespace();
+
+    let mut port_and_protocol = fields.next()?.split('/');
+    let port = port_and_protocol.next()?.parse::<u16>().ok()?;
+    let protocol = port_and_protocol.next()?;
+    if protocol.is_empty() || is_comment(protocol, opts) {
+        return None;
     }
 
-    let file = File::open(path);
-    if file.is_err() {
-        return Err("Could not open file");
+    let mut description = fields.next()?.trim_start_matches('#');
+    if description.is_empty() {
+        description = fields.next()?;
+    }
+    if !description.eq_ignore_ascii_case("unassigned")
+        && !description.eq_ignore_ascii_case("reserved")
+    {
+        return None;
     }
-    let file = file.unwrap();
 
-    let mut entries = Vec::new();
+    Some(ReservedRange {
+        port,
+        protocol: protocol.to_string(),
+    })
+}
+
+/// Counts of what was read by `validate_file_streaming` and `report`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParseStats {
+    /// All lines read, including those of included files
+    pub lines: usize,
+    /// Lines which held a valid entry
+    pub entries: usize,
+    /// Blank lines, comments, include directives and reserved rows
+    pub skipped: usize,
+}
+
+struct Parser<'a, F> {
+    opts: &'a ParseOptions,
+    on_line: F,
+    stats: ParseStats,
+    // false when only validating, so that entries aren't held in memory
+    keep_entries: bool,
+    entries: Vec<ServiceEntry>,
+    reserved: Vec<ReservedRange>,
+    // when set, errors in a line are collected here instead of stopping the parse
+    errors: Option<Vec<ParseErrorAt>>,
+    // canonical paths of the files currently being parsed, outermost first
+    #[cfg(feature = "std")]
+    include_stack: Vec<PathBuf>,
+    // canonical paths of every file parsed so far when following includes, in the order they
+    // were opened
+    #[cfg(feature = "std")]
+    files: Vec<PathBuf>,
+    // the number of the line last parsed, within its file, to report an error which stops
+    // parsing at
+    #[cfg(feature = "std")]
+    line_number: usize,
+    // lowercased name and protocol, and ports -> index into entries, used to merge duplicate
+    // definitions
+    definitions: BTreeMap<DefinitionKey, usize>,
+    // the start of a line ending in `\`, with the line number it was found at and the byte range
+    // of the lines joined so far, see `ParseOptions::line_continuations`
+    continued: Option<(String, usize, Range<usize>)>,
+}
+
+type DefinitionKey = (String, String, u16, Option<(u16, u16)>);
 
-    let lines = BufReader::new(file).lines();
-    for line in lines {
-        if let Err(_) = line {
-            return Err("Error reading file");
+impl<'a, F: FnMut(usize)> Parser<'a, F> {
+    fn new(opts: &'a ParseOptions, on_line: F) -> Self {
+        Parser {
+            opts,
+            on_line,
+            stats: ParseStats::default(),
+            keep_entries: true,
+            entries: Vec::new(),
+            reserved: Vec::new(),
+            errors: None,
+            #[cfg(feature = "std")]
+            include_stack: Vec::new(),
+            #[cfg(feature = "std")]
+            files: Vec::new(),
+            #[cfg(feature = "std")]
+            line_number: 0,
+            definitions: BTreeMap::new(),
+            continued: None,
         }
-        let line = line.unwrap();
+    }
 
-        let start = discard_ws(&line, 0);
-        let entryline = &line[start..];
-        match entryline.chars().next() {
-            Some(c) => {
-                if c == '#' {
-                    continue;
+    // Adds the aliases of `entry` to an earlier entry with the same name, protocol and port if
+    // there is one. Returns `entry` if it's a new definition.
+    fn merge_definition(&mut self, entry: ServiceEntry) -> Option<ServiceEntry> {
+        let key = (
+            entry.name.to_lowercase(),
+            entry.protocol.to_lowercase(),
+            entry.port,
+            entry
+                .port_range
+                .as_ref()
+                .map(|range| (*range.start(), *range.end())),
+        );
+        match self.definitions.get(&key) {
+            Some(&idx) => {
+                let existing = &mut self.entries[idx];
+                for alias in entry.aliases {
+                    if !existing.aliases.contains(&alias) {
+                        existing.aliases.push(alias);
+                    }
                 }
+                None
             }
-            // empty line
             None => {
-                continue;
+                self.definitions.insert(key, self.entries.len());
+                Some(entry)
             }
-        };
+        }
+    }
+
+    fn finish(self) -> (Vec<ServiceEntry>, Vec<ReservedRange>) {
+        (self.entries, self.reserved)
+    }
+
+    // Either collects the error found in `line` or stops parsing with it
+    fn line_error(&mut self, line: usize, error: ParseError) -> Result<(), ParseError> {
+        match &mut self.errors {
+            Some(errors) => {
+                errors.push(ParseErrorAt { line, error });
+                Ok(())
+            }
+            None => Err(error),
+        }
+    }
+
+    // Like `line_error`, but the line is skipped when `opts.ignore_errs` is set, or it's missing
+    // its name and `opts.ignore_missing_names` is set, and errors aren't being collected
+    fn bad_line(&mut self, line: usize, error: ParseError) -> Result<(), ParseError> {
+        let ignored = self.opts.ignore_errs
+            || (self.opts.ignore_missing_names
+                && matches!(error, ParseError::MissingServiceName(_)));
+        if ignored && self.errors.is_none() {
+            return Ok(());
+        }
+        self.line_error(line, error)
+    }
+
+    // Counts a line towards `stats`, calling `on_line` if it's time to report progress
+    fn count_line(&mut self) {
+        self.stats.lines += 1;
+        let interval = self.opts.progress_interval;
+        if interval > 0 && self.stats.lines.is_multiple_of(interval) {
+            (self.on_line)(self.stats.lines);
+        }
+    }
+
+    // Parses the next line of the input, without its line ending, starting at byte `line_offset`
+    fn feed_line(
+        &mut self,
+        line: &str,
+        line_number: usize,
+        line_offset: usize,
+    ) -> Result<(), ParseError> {
+        self.count_line();
+        let lines = line_offset..line_offset + line.len();
+        if !self.opts.line_continuations {
+            return self.parse_line(line, line_number, lines);
+        }
+
+        match (self.continued.take(), line.strip_suffix('\\')) {
+            (None, None) => self.parse_line(line, line_number, lines),
+            (None, Some(start)) => {
+                self.continued = Some((start.to_string(), line_number, lines));
+                Ok(())
+            }
+            (Some((mut joined, number, joined_lines)), continues) => {
+                // the `\` separates the fields on either side of it, like a space
+                joined.push(' ');
+                joined.push_str(continues.unwrap_or(line));
+                let lines = joined_lines.start..lines.end;
+                if continues.is_some() {
+                    self.continued = Some((joined, number, lines));
+                    return Ok(());
+                }
+                self.parse_line(&joined, number, lines)
+            }
+        }
+    }
+
+    // Parses a line left continued at the end of the input
+    fn finish_lines(&mut self) -> Result<(), ParseError> {
+        match self.continued.take() {
+            Some((line, number, lines)) => self.parse_line(&line, number, lines),
+            None => Ok(()),
+        }
+    }
 
-        match entryline.parse() {
-            Ok(entry) => {
-                entries.push(entry);
+    // Parses a single logical line, which has already been counted, read from the byte range
+    // `lines` of the input
+    fn parse_line(
+        &mut self,
+        line: &str,
+        line_number: usize,
+        lines: Range<usize>,
+    ) -> Result<(), ParseError> {
+        let opts = self.opts;
+        #[cfg(feature = "std")]
+        {
+            self.line_number = line_number;
+        }
+
+        let start = discard_ws(line, 0);
+        let entryline = &line[start..];
+        // comment or empty line
+        if entryline.is_empty() || is_comment(entryline, opts) {
+            // e.g. `#http 80/tcp`, an entry which has been commented out
+            if opts.parse_disabled && entryline.starts_with('#') {
+                if let Ok(entry) = parse_entry(&entryline[1..], opts) {
+                    let entry = ServiceEntry {
+                        disabled: true,
+                        ..entry
+                    };
+                    return self.add_entry(entry, line, start, lines);
+                }
             }
-            Err(msg) => {
-                if !ignore_errs {
-                    return Err(msg);
+            self.stats.skipped += 1;
+            return Ok(());
+        }
+
+        if opts.allow_merge_markers && entryline.starts_with('+') {
+            self.stats.skipped += 1;
+            return match self.merge_marker(entryline) {
+                Ok(()) => Ok(()),
+                Err(err) => self.bad_line(line_number, err),
+            };
+        }
+
+        #[cfg(feature = "std")]
+        if opts.follow_includes {
+            let mut fields = entryline.split_whitespace();
+            match (fields.next(), fields.next()) {
+                // e.g. `include 1234/tcp` is an entry for a service named `include`
+                (Some("include"), Some(target)) if !is_port_protocol(target, opts) => {
+                    self.stats.skipped += 1;
+                    if let Err(err) = self.include(target) {
+                        self.line_error(line_number, err)?;
+                    }
+                    return Ok(());
                 }
+                _ => {}
             }
+        }
+
+        if opts.capture_reserved {
+            if let Some(range) = parse_reserved(entryline, opts) {
+                self.stats.skipped += 1;
+                self.reserved.push(range);
+                return Ok(());
+            }
+        }
+
+        match parse_entry(entryline, opts) {
+            Ok(entry) => self.add_entry(entry, line, start, lines),
+            Err(err) => self.bad_line(line_number, err),
+        }
+    }
+
+    // Adds the aliases of a `+name alias...` line to the earlier entries called `name`
+    fn merge_marker(&mut self, line: &str) -> Result<(), ParseError> {
+        let opts = self.opts;
+        let mut fields = line[1..].split_whitespace();
+        let name = match fields.next() {
+            Some(name) if !is_comment(name, opts) => name.to_lowercase(),
+            _ => return Err(ParseError::MalformedInput(line.trim().to_string())),
         };
+
+        let mut aliases = Vec::new();
+        for alias in fields {
+            match comment_start(alias, opts) {
+                Some(idx) => {
+                    aliases.extend(Some(&alias[..idx]).filter(|alias| !alias.is_empty()));
+                    break;
+                }
+                None => aliases.push(alias),
+            }
+        }
+
+        // without kept entries there's nothing to merge into or check against
+        if !self.keep_entries {
+            return Ok(());
+        }
+        let mut found = false;
+        for entry in &mut self.entries {
+            if entry.name.to_lowercase() != name {
+                continue;
+            }
+            found = true;
+            for alias in &aliases {
+                let alias = fold_name(alias, opts);
+                if !entry.aliases.contains(&alias) {
+                    entry.aliases.push(alias);
+                }
+            }
+        }
+        if found {
+            Ok(())
+        } else {
+            Err(ParseError::UnknownMergeTarget(name))
+        }
+    }
+
+    // Adds an entry parsed from `line`, whose service name is at byte `start`
+    fn add_entry(
+        &mut self,
+        mut entry: ServiceEntry,
+        line: &str,
+        start: usize,
+        lines: Range<usize>,
+    ) -> Result<(), ParseError> {
+        let opts = self.opts;
+        if opts.keep_raw {
+            entry.raw = Some(line.to_string());
+        }
+        if opts.track_spans {
+            entry.span = Some(lines.start + start..lines.end);
+        }
+
+        // e.g. `myproto 9000/tcp,udp`, which becomes one entry per protocol
+        if opts.allow_comma_protocols && entry.protocol.contains(',') {
+            let protocols: Vec<_> = entry.protocol.split(',').map(String::from).collect();
+            let raw_protocols: Vec<_> = match &entry.protocol_raw {
+                Some(raw) => raw.split(',').map(|raw| Some(raw.to_string())).collect(),
+                None => vec![None; protocols.len()],
+            };
+            for (protocol, protocol_raw) in protocols.into_iter().zip(raw_protocols) {
+                self.push_entry(ServiceEntry {
+                    protocol,
+                    protocol_raw,
+                    ..entry.clone()
+                })?;
+            }
+            Ok(())
+        } else {
+            self.push_entry(entry)
+        }
+    }
+
+    fn push_entry(&mut self, mut entry: ServiceEntry) -> Result<(), ParseError> {
+        if let Some(max) = self.opts.max_total_entries {
+            if self.stats.entries >= max {
+                return Err(ParseError::TooManyEntries(max));
+            }
+        }
+        self.stats.entries += 1;
+        if !self.keep_entries {
+            return Ok(());
+        }
+        if self.opts.merge_duplicate_definitions {
+            match self.merge_definition(entry) {
+                Some(new_entry) => entry = new_entry,
+                None => return Ok(()),
+            }
+        }
+        self.entries.push(entry);
+        Ok(())
     }
+}
 
-    Ok(entries)
+/// Parse services from a string using the format described in `man services(5)`
+/// Unlike the other parse functions this is available without the `std` feature, in which case
+/// `opts.follow_includes` has no effect.
+pub fn parse_str(s: &str, opts: &ParseOptions) -> Result<Vec<ServiceEntry>, ParseError> {
+    let mut parser = Parser::new(opts, |_| {});
+    let mut offset = 0;
+    for (idx, line) in s.split_inclusive('\n').enumerate() {
+        parser.feed_line(strip_line_ending(line), idx + 1, offset)?;
+        offset += line.len();
+    }
+    parser.finish_lines()?;
+    Ok(parser.finish().0)
 }
 
-/// Parse /etc/services
-pub fn parse_servicefile(ignore_errs: bool) -> Result<Vec<ServiceEntry>, &'static str> {
-    parse_file(&Path::new("/etc/services"), ignore_errs)
+// Line endings are stripped the same way as `BufRead::lines`
+fn strip_line_ending(line: &str) -> &str {
+    match line.strip_suffix('\n') {
+        Some(line) => line.strip_suffix('\r').unwrap_or(line),
+        None => line,
+    }
 }
 
 #[cfg(test)]
@@ -171,7 +1257,8 @@ mod tests {
     extern crate mktemp;
     use mktemp::Temp;
 
-    use std::io::{Seek, SeekFrom, Write};
+    use std::fs::{self, File};
+    use std::io::{self, Seek, SeekFrom, Write};
 
     use super::*;
 
@@ -184,6 +1271,7 @@ mod tests {
                 port: 1,
                 protocol: "tcp".to_string(),
                 aliases: vec!(),
+                ..Default::default()
             })
         );
     }
@@ -197,141 +1285,1976 @@ mod tests {
                 port: 1,
                 protocol: "tcp".to_string(),
                 aliases: vec!("tcpmultiplexer".to_string(), "niceservice".to_string()),
+                ..Default::default()
             })
         );
     }
 
     #[test]
-    fn test_parse_file() {
-        let temp_file = Temp::new_file().unwrap();
-        let temp_path = temp_file.as_path();
-        let mut file = File::create(temp_path).unwrap();
+    fn parse_no_aliases() {
+        let line = "http 80/TCP www www-http # World Wide Web";
+        let entry = parse_line_no_aliases(line).unwrap();
+        assert_eq!(entry.aliases, Vec::<String>::new());
+        assert_eq!(
+            entry,
+            ServiceEntry {
+                aliases: vec!(),
+                ..line.parse().unwrap()
+            }
+        );
 
-        write!(
-            file,
-            "\
-                # WELL KNOWN PORT NUMBERS\n\
-                #
-                rtmp              1/ddp    #Routing Table Maintenance Protocol\n\
-                tcpmux            1/udp     # TCP Port Service Multiplexer\n\
-                tcpmux            1/tcp     # TCP Port Service Multiplexer\n\
-                #                          Mark Lottor <MKL@nisc.sri.com>\n\
-                nbp               2/ddp    #Name Binding Protocol\n\
-                compressnet       2/udp     # Management Utility\n\
-                compressnet       2/tcp     # Management Utility\n\
-                compressnet       3/udp     # Compression Process\n\
-                compressnet       3/tcp     # Compression Process\n\
-            "
-        )
-        .expect("Could not write to temp file");
         assert_eq!(
-            parse_file(&temp_path, false),
+            parse_line_no_aliases("http"),
+            Err(ParseError::MissingPortProtocol("http".to_string()))
+        );
+    }
+
+    #[test]
+    fn iana_line() {
+        let opts = ParseOptions {
+            keep_comments: true,
+            ..Default::default()
+        };
+        let line = "ftp 21/tcp fsp fspd # File Transfer [Control]";
+        assert_eq!(
+            super::parse_entry(line, &opts).unwrap().to_iana_line(),
+            "ftp            21/tcp         fsp fspd # File Transfer [Control]"
+        );
+
+        let entry: ServiceEntry = "http 80/tcp".parse().unwrap();
+        assert_eq!(entry.to_iana_line(), "http           80/tcp");
+
+        let entry: ServiceEntry = "averyveryverylongname 4000/tcp".parse().unwrap();
+        assert_eq!(entry.to_iana_line(), "averyveryverylongname 4000/tcp");
+    }
+
+    #[test]
+    fn escaped_name() {
+        let entry: ServiceEntry = "http 80/tcp".parse().unwrap();
+        assert!(matches!(entry.escaped_name(), Cow::Borrowed("http")));
+
+        let entry = ServiceEntry {
+            name: "my service\t2".to_string(),
+            ..entry
+        };
+        assert_eq!(entry.escaped_name(), "my_service_2");
+    }
+
+    #[test]
+    fn parse_entry_lowercase_names() {
+        let opts = ParseOptions {
+            lowercase_names: true,
+            ..Default::default()
+        };
+        let entry = super::parse_entry("HTTP 80/tcp WWW Www-Http#web", &opts).unwrap();
+        assert_eq!(entry.name, "http");
+        assert_eq!(
+            entry.aliases,
+            vec!("www".to_string(), "www-http".to_string())
+        );
+
+        let entry: ServiceEntry = "HTTP 80/tcp WWW".parse().unwrap();
+        assert_eq!(entry.name, "HTTP");
+        assert_eq!(entry.aliases, vec!("WWW".to_string()));
+    }
+
+    #[test]
+    fn parse_entry_proto_port_order() {
+        let opts = ParseOptions {
+            proto_port_order: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            super::parse_entry("http tcp/80 www # web", &opts),
+            Ok("http 80/tcp www".parse().unwrap())
+        );
+        assert_eq!(
+            super::parse_entry("http 80/tcp", &opts),
+            Err(ParseError::MalformedPort("80/tcp".to_string()))
+        );
+        assert_eq!(
+            super::parse_entry("http tcp/udp", &opts),
+            Err(ParseError::MalformedPort("udp".to_string()))
+        );
+        assert_eq!(
+            "http tcp/80".parse::<ServiceEntry>(),
+            Err(ParseError::MalformedPort("tcp".to_string()))
+        );
+    }
+
+    #[test]
+    fn alias_diff() {
+        let old: ServiceEntry = "http 80/tcp www".parse().unwrap();
+        let new: ServiceEntry = "http 80/tcp www www-http".parse().unwrap();
+        assert_eq!(old.alias_diff(&new), (vec!("www-http"), vec!()));
+        assert_eq!(new.alias_diff(&old), (vec!(), vec!("www-http")));
+
+        let other: ServiceEntry = "http 80/tcp web".parse().unwrap();
+        assert_eq!(old.alias_diff(&other), (vec!("web"), vec!("www")));
+        assert_eq!(old.alias_diff(&old), (vec!(), vec!()));
+    }
+
+    #[test]
+    fn parse_entry_port_range() {
+        assert_eq!(
+            "myservice 8000-8010/tcp".parse::<ServiceEntry>(),
+            Err(ParseError::MalformedPort("8000-8010".to_string()))
+        );
+        assert_eq!(
+            "myservice 65536/tcp".parse::<ServiceEntry>(),
+            Err(ParseError::MalformedPort("65536".to_string()))
+        );
+
+        let opts = ParseOptions {
+            allow_port_ranges: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            super::parse_entry("myservice 8000-8010/tcp", &opts),
+            Ok(ServiceEntry {
+                name: "myservice".to_string(),
+                port: 8000,
+                protocol: "tcp".to_string(),
+                port_range: Some(8000..=8010),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            super::parse_entry("http 80/tcp", &opts).unwrap().port_range,
+            None
+        );
+        assert_eq!(
+            super::parse_entry("myservice 8010-8000/tcp", &opts),
+            Err(ParseError::MalformedPort("8010-8000".to_string()))
+        );
+        assert_eq!(
+            super::parse_entry("myservice 8000-/tcp", &opts),
+            Err(ParseError::MalformedPort("8000-".to_string()))
+        );
+    }
+
+    #[test]
+    fn expand_range() {
+        let opts = ParseOptions {
+            allow_port_ranges: true,
+            ..Default::default()
+        };
+        let entry = super::parse_entry("myservice 8000-8002/tcp mine", &opts).unwrap();
+        let ports: Vec<_> = entry.expand_range().iter().map(|e| e.port).collect();
+        assert_eq!(ports, vec!(8000, 8001, 8002));
+        assert!(entry
+            .expand_range()
+            .iter()
+            .all(|e| e.port_range.is_none() && e.aliases == vec!("mine")));
+
+        let entry: ServiceEntry = "http 80/tcp".parse().unwrap();
+        assert_eq!(entry.expand_range(), vec!(entry));
+    }
+
+    #[test]
+    fn parse_entry_hash_in_aliases() {
+        let aliases =
+            |line: &str, opts: &ParseOptions| super::parse_entry(line, opts).unwrap().aliases;
+        let opts = ParseOptions::default();
+        assert_eq!(
+            aliases("http 80/tcp www foo#bar baz", &opts),
+            vec!("www", "foo")
+        );
+        assert_eq!(aliases("http 80/tcp www #bar baz", &opts), vec!("www"));
+        assert_eq!(
+            aliases("http 80/tcp www foo #bar", &opts),
+            vec!("www", "foo")
+        );
+        assert_eq!(aliases("http 80/tcp #www", &opts), Vec::<String>::new());
+        assert_eq!(aliases("http 80/tcp#www web", &opts), Vec::<String>::new());
+        assert_eq!(
+            super::parse_entry("http 80/tcp#www", &opts)
+                .unwrap()
+                .protocol,
+            "tcp"
+        );
+        assert_eq!(
+            super::parse_entry("http 80/#tcp", &opts),
+            Err(ParseError::MissingProtocol("80/".to_string()))
+        );
+
+        let opts = ParseOptions {
+            literal_hash_in_aliases: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            aliases("http 80/tcp www foo#bar baz", &opts),
+            vec!("www", "foo#bar", "baz")
+        );
+        assert_eq!(aliases("http 80/tcp#www web", &opts), Vec::<String>::new());
+        assert_eq!(aliases("http 80/tcp www #bar baz", &opts), vec!("www"));
+        assert_eq!(
+            aliases("http 80/tcp www foo #bar", &opts),
+            vec!("www", "foo")
+        );
+
+        let opts = ParseOptions {
+            also_treat_semicolon_as_comment: true,
+            ..Default::default()
+        };
+        assert_eq!(aliases("http 80/tcp www;web", &opts), vec!("www"));
+    }
+
+    #[test]
+    fn parse_entry_trailing_semicolon() {
+        let opts = ParseOptions {
+            strip_trailing_semicolon: true,
+            ..Default::default()
+        };
+        let http: ServiceEntry = "http 80/tcp".parse().unwrap();
+        assert_eq!(super::parse_entry("http 80/tcp;", &opts), Ok(http.clone()));
+        assert_eq!(
+            super::parse_entry("http 80/tcp; \t", &opts),
+            Ok(http.clone())
+        );
+        assert_eq!(
+            super::parse_entry("http 80/tcp; # web", &opts),
+            Ok(http.clone())
+        );
+        assert_eq!(super::parse_entry("http 80/tcp;#web", &opts), Ok(http));
+        let opts_with_comments = ParseOptions {
+            keep_comments: true,
+            literal_hash_in_aliases: true,
+            ..opts.clone()
+        };
+        let entry = super::parse_entry("http 80/tcp w#w;  # web;", &opts_with_comments).unwrap();
+        assert_eq!(entry.aliases, vec!("w#w"));
+        assert_eq!(entry.comment.as_deref(), Some("web;"));
+        assert_eq!(
+            super::parse_entry("http 80/tcp www;", &opts)
+                .unwrap()
+                .aliases,
+            vec!("www")
+        );
+        assert_eq!(
+            super::parse_entry("http 80/;", &opts),
+            Err(ParseError::MissingProtocol("80/".to_string()))
+        );
+
+        let entry: ServiceEntry = "http 80/tcp;".parse().unwrap();
+        assert_eq!(entry.protocol, "tcp;");
+        assert!(!entry.is_standard_transport());
+    }
+
+    #[test]
+    fn parse_entry_original_protocol() {
+        let opts = ParseOptions {
+            preserve_original_protocol: true,
+            protocol_case: Case::Upper,
+            ..Default::default()
+        };
+        let entry = super::parse_entry("HTTP 80/TCP", &opts).unwrap();
+        assert_eq!(entry.protocol, "tcp");
+        assert_eq!(entry.protocol_raw.as_deref(), Some("TCP"));
+        assert_eq!(entry.port_protocol(), "80/tcp");
+
+        let opts = ParseOptions {
+            preserve_original_protocol: true,
+            allow_comma_protocols: true,
+            ..Default::default()
+        };
+        let entries = super::parse_str("myproto 9000/Tcp,UDP\n", &opts).unwrap();
+        let protocols: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.protocol.as_str(), entry.protocol_raw.as_deref()))
+            .collect();
+        assert_eq!(protocols, vec!(("tcp", Some("Tcp")), ("udp", Some("UDP"))));
+
+        let entry: ServiceEntry = "HTTP 80/TCP".parse().unwrap();
+        assert_eq!(entry.protocol, "tcp");
+        assert_eq!(entry.protocol_raw, None);
+    }
+
+    #[test]
+    fn parse_entry_protocol_synonyms() {
+        let opts = ParseOptions {
+            protocol_synonyms: vec![("tcp6".to_string(), "tcp".to_string())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let protocol = |line: &str| super::parse_entry(line, &opts).unwrap().protocol;
+        assert_eq!(protocol("http 80/tcp6 www"), "tcp");
+        assert_eq!(protocol("http 80/TCP6"), "tcp");
+        assert_eq!(protocol("http 80/udp6"), "udp6");
+        assert_eq!(
+            "http 80/tcp6".parse::<ServiceEntry>().unwrap().protocol,
+            "tcp6"
+        );
+    }
+
+    #[test]
+    fn parse_entry_protocol_suffix() {
+        let opts = ParseOptions {
+            split_protocol_suffix: true,
+            ..Default::default()
+        };
+        let entry = super::parse_entry("http 80/TCP%eth0 www", &opts).unwrap();
+        assert_eq!(entry.protocol, "tcp");
+        assert_eq!(entry.protocol_suffix, Some("eth0".to_string()));
+        assert_eq!(entry.aliases, vec!("www"));
+        assert_eq!(entry.port_protocol(), "80/tcp%eth0");
+
+        assert_eq!(
+            super::parse_entry("http 80/tcp%", &opts)
+                .unwrap()
+                .protocol_suffix,
+            None
+        );
+        assert_eq!(
+            super::parse_entry("http 80/%eth0", &opts),
+            Err(ParseError::MissingProtocol("80/".to_string()))
+        );
+
+        let entry: ServiceEntry = "http 80/tcp%eth0".parse().unwrap();
+        assert_eq!(entry.protocol, "tcp%eth0");
+        assert_eq!(entry.protocol_suffix, None);
+    }
+
+    #[test]
+    fn parse_entry_port_first() {
+        let opts = ParseOptions {
+            field_order: FieldOrder::PortFirst,
+            ..Default::default()
+        };
+        let expected = super::parse_entry("http 80/tcp www www-http", &ParseOptions::default());
+        assert_eq!(
+            super::parse_entry("80/tcp http www www-http", &opts),
+            expected
+        );
+        assert_eq!(
+            super::parse_entry("80 http tcp www www-http # web", &opts),
+            expected
+        );
+
+        assert_eq!(
+            super::parse_entry("80/tcp", &opts),
+            Err(ParseError::MalformedInput("80/tcp".to_string()))
+        );
+        assert_eq!(
+            super::parse_entry("80 http", &opts),
+            Err(ParseError::MissingProtocol("80".to_string()))
+        );
+        assert_eq!(
+            super::parse_entry("http 80/tcp", &opts),
+            Err(ParseError::MalformedPort("http".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_entry_space_before_protocol() {
+        assert_eq!(
+            "foo 80 tcp".parse::<ServiceEntry>(),
+            Err(ParseError::MissingProtocol("80".to_string()))
+        );
+
+        let opts = ParseOptions {
+            allow_space_before_protocol: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            super::parse_entry("foo 80 tcp bar # comment", &opts),
+            Ok(ServiceEntry {
+                name: "foo".to_string(),
+                port: 80,
+                protocol: "tcp".to_string(),
+                aliases: vec!("bar".to_string()),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            super::parse_entry("foo 80/tcp bar", &opts),
+            "foo 80/tcp bar".parse()
+        );
+        assert_eq!(
+            super::parse_entry("foo 80 # tcp", &opts),
+            Err(ParseError::MissingProtocol("80".to_string()))
+        );
+        assert_eq!(
+            super::parse_entry("foo 80", &opts),
+            Err(ParseError::MissingProtocol("80".to_string()))
+        );
+    }
+
+    #[test]
+    fn compare_entry_to_line() {
+        let entry: ServiceEntry = "http 80/tcp www www-http".parse().unwrap();
+        assert_eq!(entry, "http 80/tcp www www-http");
+        assert_eq!(entry, "  http\t80/tcp   www www-http  # World Wide Web");
+        assert_ne!(entry, "http 80/tcp www");
+        assert_ne!(entry, "http 80/udp www www-http");
+        assert_ne!(entry, "http");
+        assert!(entry == *"http 80/tcp www www-http");
+
+        let opts = ParseOptions {
+            keep_raw: true,
+            ..Default::default()
+        };
+        let entries = parse_reader("http 80/tcp www www-http\n".as_bytes(), &opts).unwrap();
+        assert_eq!(entries[0], "http   80/tcp www www-http");
+    }
+
+    #[test]
+    fn entry_key() {
+        let entry: ServiceEntry = "http 80/tcp www".parse().unwrap();
+        assert_eq!(entry.key(), "http/tcp");
+        let other = ServiceEntry {
+            name: "HTTP".to_string(),
+            port: 8080,
+            protocol: "TCP".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(other.key(), entry.key());
+        assert_ne!(entry.clone().with_protocol("udp").key(), entry.key());
+    }
+
+    #[test]
+    fn port_protocol() {
+        let entry: ServiceEntry = "http 80/tcp www".parse().unwrap();
+        assert_eq!(entry.port_protocol(), "80/tcp");
+
+        let entry = ServiceEntry {
+            port_range: Some(8000..=8010),
+            ..super::parse_entry("myservice 8000/udp", &ParseOptions::default()).unwrap()
+        };
+        assert_eq!(entry.port_protocol(), "8000-8010/udp");
+    }
+
+    #[test]
+    fn parse_entry_comment() {
+        let opts = ParseOptions {
+            keep_comments: true,
+            ..Default::default()
+        };
+        let comment = |line: &str| super::parse_entry(line, &opts).unwrap().comment;
+        assert_eq!(
+            comment("http 80/tcp www  # World Wide Web HTTP "),
+            Some("World Wide Web HTTP".to_string())
+        );
+        assert_eq!(comment("http 80/tcp #WWW"), Some("WWW".to_string()));
+        assert_eq!(comment("http 80/tcp www#web"), Some("web".to_string()));
+        assert_eq!(comment("http 80/tcp#web"), Some("web".to_string()));
+        assert_eq!(comment("http 80/tcp www #"), None);
+        assert_eq!(comment("http 80/tcp www"), None);
+
+        let entry: ServiceEntry = "http 80/tcp www # World Wide Web".parse().unwrap();
+        assert_eq!(entry.comment, None);
+    }
+
+    #[test]
+    fn eq_normalized() {
+        let opts = ParseOptions {
+            protocol_case: Case::Preserve,
+            keep_comments: true,
+            ..Default::default()
+        };
+        let entry = |line: &str| super::parse_entry(line, &opts).unwrap();
+        let a = entry("http 80/TCP www # web");
+        let b = entry("HTTP 80/tcp WWW");
+        assert!(a != b);
+        assert!(a.eq_normalized(&b));
+        assert!(b.eq_normalized(&a));
+
+        assert!(!a.eq_normalized(&entry("http 80/udp www")));
+        assert!(!a.eq_normalized(&entry("http 80/tcp")));
+        assert!(!a.eq_normalized(&entry("http 8080/tcp www")));
+    }
+
+    #[test]
+    fn rename_entry() {
+        let mut entry: ServiceEntry = "http 80/tcp www".parse().unwrap();
+        entry.rename("webserver", true);
+        assert_eq!(entry.name, "webserver");
+        assert_eq!(entry.aliases, vec!("www", "http"));
+
+        entry.rename("web", false);
+        assert_eq!(entry.name, "web");
+        assert_eq!(entry.aliases, vec!("www", "http"));
+
+        entry.rename("http", true);
+        entry.rename("http", true);
+        assert_eq!(entry.name, "http");
+        assert_eq!(entry.aliases, vec!("www", "web"));
+    }
+
+    #[test]
+    fn remove_alias() {
+        let mut entry: ServiceEntry = "http 80/tcp www www-http".parse().unwrap();
+        assert!(entry.remove_alias("www"));
+        assert_eq!(entry.aliases, vec!("www-http"));
+        assert!(!entry.remove_alias("www"));
+        assert!(!entry.remove_alias("http"));
+
+        let mut entry: ServiceEntry = "http 80/tcp web WWW www-http www".parse().unwrap();
+        assert!(entry.remove_alias("Www"));
+        assert_eq!(entry.aliases, vec!("web", "www-http"));
+    }
+
+    #[test]
+    fn socket_addr() {
+        let entry: ServiceEntry = "http 80/tcp www".parse().unwrap();
+        let addr = entry.socket_addr(IpAddr::from([127, 0, 0, 1]));
+        assert_eq!(addr.to_string(), "127.0.0.1:80");
+        assert!(addr.ip().is_loopback());
+    }
+
+    #[test]
+    fn comment_tags() {
+        let opts = ParseOptions {
+            keep_comments: true,
+            ..Default::default()
+        };
+        let entry = super::parse_entry(
+            "mysvc 9000/tcp # My service owner=team-x tier=backend =x",
+            &opts,
+        )
+        .unwrap();
+        let tags = entry.comment_tags();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags["owner"], "team-x");
+        assert_eq!(tags["tier"], "backend");
+
+        assert!("mysvc 9000/tcp"
+            .parse::<ServiceEntry>()
+            .unwrap()
+            .comment_tags()
+            .is_empty());
+    }
+
+    #[test]
+    fn description() {
+        let opts = ParseOptions {
+            keep_comments: true,
+            ..Default::default()
+        };
+        let description = |line: &str| {
+            let entry = super::parse_entry(line, &opts).unwrap();
+            entry.description().map(str::to_string)
+        };
+        assert_eq!(
+            description("http 80/tcp # World Wide Web HTTP owner=web tier=frontend"),
+            Some("World Wide Web HTTP".to_string())
+        );
+        assert_eq!(
+            description("http 80/tcp # owner=web  World Wide Web"),
+            Some("World Wide Web".to_string())
+        );
+        assert_eq!(
+            description("http 80/tcp # World owner=web Wide Web"),
+            Some("World owner=web Wide Web".to_string())
+        );
+        assert_eq!(description("http 80/tcp # owner=web"), None);
+        assert_eq!(description("http 80/tcp"), None);
+    }
+
+    #[test]
+    fn parse_entry_protocol_case() {
+        let protocol = |protocol_case| {
+            let opts = ParseOptions {
+                protocol_case,
+                ..Default::default()
+            };
+            super::parse_entry("HTTP 80/TCP WWW", &opts)
+                .unwrap()
+                .protocol
+        };
+        assert_eq!(protocol(Case::Lower), "tcp");
+        assert_eq!(protocol(Case::Upper), "TCP");
+        assert_eq!(protocol(Case::Preserve), "TCP");
+
+        let entry: ServiceEntry = "HTTP 80/Tcp WWW".parse().unwrap();
+        assert_eq!(entry.protocol, "tcp");
+        assert_eq!(entry.name, "HTTP");
+    }
+
+    #[test]
+    fn derive_entries() {
+        let tcp: ServiceEntry = "http 80/tcp www www-http".parse().unwrap();
+
+        let udp = tcp.clone().with_protocol("udp");
+        assert_eq!(udp, "http 80/udp www www-http");
+        assert_eq!(
+            ServiceEntry {
+                protocol: "tcp".to_string(),
+                ..udp
+            },
+            tcp
+        );
+
+        assert_eq!(tcp.with_port(8080), "http 8080/tcp www www-http");
+    }
+
+    #[test]
+    fn indented_comment() {
+        assert!(line_is_comment("# foo"));
+        assert!(line_is_comment("   # foo"));
+        assert!(line_is_comment("\t#foo"));
+        assert!(line_is_comment("\u{a0} # foo"));
+        assert!(!line_is_comment("http 80/tcp # foo"));
+        assert!(!line_is_comment("   "));
+        assert!(!line_is_comment("  ; foo"));
+    }
+
+    #[test]
+    fn normalize_entry() {
+        let mut entry: ServiceEntry = "HTTP 80/TCP WWW".parse().unwrap();
+        entry.normalize(false);
+        assert_eq!(
+            entry,
+            ServiceEntry {
+                name: "HTTP".to_string(),
+                port: 80,
+                protocol: "tcp".to_string(),
+                aliases: vec!("WWW".to_string()),
+                ..Default::default()
+            }
+        );
+
+        entry.normalize(true);
+        assert_eq!(entry.name, "http");
+        assert_eq!(entry.aliases, vec!("www".to_string()));
+
+        let mut entry = ServiceEntry {
+            name: " http".to_string(),
+            port: 80,
+            protocol: "Tcp ".to_string(),
+            ..Default::default()
+        };
+        entry.normalize(false);
+        assert_eq!(entry.name, "http");
+        assert_eq!(entry.protocol, "tcp");
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let temp_file = Temp::new_file().unwrap();
+        let temp_path = temp_file.as_path();
+        let mut file = File::create(temp_path).unwrap();
+
+        write!(
+            file,
+            "\
+                # WELL KNOWN PORT NUMBERS\n\
+                #
+                rtmp              1/ddp    #Routing Table Maintenance Protocol\n\
+                tcpmux            1/udp     # TCP Port Service Multiplexer\n\
+                tcpmux            1/tcp     # TCP Port Service Multiplexer\n\
+                #                          Mark Lottor <MKL@nisc.sri.com>\n\
+                nbp               2/ddp    #Name Binding Protocol\n\
+                compressnet       2/udp     # Management Utility\n\
+                compressnet       2/tcp     # Management Utility\n\
+                compressnet       3/udp     # Compression Process\n\
+                compressnet       3/tcp     # Compression Process\n\
+            "
+        )
+        .expect("Could not write to temp file");
+        assert_eq!(
+            parse_file(temp_path, false),
+            Ok(vec!(
+                ServiceEntry {
+                    name: "rtmp".to_string(),
+                    port: 1,
+                    protocol: "ddp".to_string(),
+                    aliases: vec!(),
+                    ..Default::default()
+                },
+                ServiceEntry {
+                    name: "tcpmux".to_string(),
+                    port: 1,
+                    protocol: "udp".to_string(),
+                    aliases: vec!(),
+                    ..Default::default()
+                },
+                ServiceEntry {
+                    name: "tcpmux".to_string(),
+                    port: 1,
+                    protocol: "tcp".to_string(),
+                    aliases: vec!(),
+                    ..Default::default()
+                },
+                ServiceEntry {
+                    name: "nbp".to_string(),
+                    port: 2,
+                    protocol: "ddp".to_string(),
+                    aliases: vec!(),
+                    ..Default::default()
+                },
+                ServiceEntry {
+                    name: "compressnet".to_string(),
+                    port: 2,
+                    protocol: "udp".to_string(),
+                    aliases: vec!(),
+                    ..Default::default()
+                },
+                ServiceEntry {
+                    name: "compressnet".to_string(),
+                    port: 2,
+                    protocol: "tcp".to_string(),
+                    aliases: vec!(),
+                    ..Default::default()
+                },
+                ServiceEntry {
+                    name: "compressnet".to_string(),
+                    port: 3,
+                    protocol: "udp".to_string(),
+                    aliases: vec!(),
+                    ..Default::default()
+                },
+                ServiceEntry {
+                    name: "compressnet".to_string(),
+                    port: 3,
+                    protocol: "tcp".to_string(),
+                    aliases: vec!(),
+                    ..Default::default()
+                },
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_file_errors() {
+        let temp_file = Temp::new_file().unwrap();
+        let temp_path = temp_file.as_path();
+        let mut file = File::create(temp_path).unwrap();
+
+        writeln!(file, "service").expect("");
+        assert_eq!(
+            parse_file(temp_path, false),
+            Err(ParseError::MissingPortProtocol("service".to_string()))
+        );
+
+        file.set_len(0).expect("");
+        file.seek(SeekFrom::Start(0)).expect("");
+        writeln!(file, "service # 1/tcp").expect("");
+        assert_eq!(
+            parse_file(temp_path, false),
+            Err(ParseError::MissingPortProtocol("service".to_string()))
+        );
+
+        file.set_len(0).expect("");
+        file.seek(SeekFrom::Start(0)).expect("");
+        writeln!(file, "service  1#/tcp").expect("");
+        assert_eq!(
+            parse_file(temp_path, false),
+            Err(ParseError::MalformedPort("1#".to_string()))
+        );
+
+        file.set_len(0).expect("");
+        file.seek(SeekFrom::Start(0)).expect("");
+        writeln!(file, "service  1/#tcp").expect("");
+        assert_eq!(
+            parse_file(temp_path, false),
+            Err(ParseError::MissingProtocol("1/".to_string()))
+        );
+
+        file.set_len(0).expect("");
+        file.seek(SeekFrom::Start(0)).expect("");
+        writeln!(file, "service  80/").expect("");
+        assert_eq!(
+            parse_file(temp_path, false),
+            Err(ParseError::MissingProtocol("80/".to_string()))
+        );
+
+        file.set_len(0).expect("");
+        file.seek(SeekFrom::Start(0)).expect("");
+        writeln!(file, "service  80").expect("");
+        assert_eq!(
+            parse_file(temp_path, false),
+            Err(ParseError::MissingProtocol("80".to_string()))
+        );
+
+        file.set_len(0).expect("");
+        file.seek(SeekFrom::Start(0)).expect("");
+        writeln!(file, "service asdf/tcp").expect("");
+        assert_eq!(
+            parse_file(temp_path, false),
+            Err(ParseError::MalformedPort("asdf".to_string()))
+        );
+
+        file.set_len(0).expect("");
+        file.seek(SeekFrom::Start(0)).expect("");
+        writeln!(file, "service asdf/").expect("");
+        assert_eq!(
+            parse_file(temp_path, false),
+            Err(ParseError::MalformedPort("asdf".to_string()))
+        );
+
+        let temp_dir = Temp::new_dir().unwrap();
+        let temp_dir_path = temp_dir.as_path();
+        assert_eq!(
+            parse_file(temp_dir_path, false),
+            Err(ParseError::NotARegularFile {
+                path: temp_dir_path.to_path_buf()
+            })
+        );
+
+        let missing = temp_dir_path.join("services");
+        assert_eq!(
+            parse_file(&missing, false),
+            Err(ParseError::FileNotFound { path: missing })
+        );
+    }
+
+    #[test]
+    fn test_parse_file_semicolon_comments() {
+        let temp_file = Temp::new_file().unwrap();
+        let temp_path = temp_file.as_path();
+        let mut file = File::create(temp_path).unwrap();
+
+        write!(
+            file,
+            "\
+                # upstream documentation\n\
+                ; local overlay\n\
+                tcpmux            1/tcp    ; TCP Port Service Multiplexer\n\
+                nbp               2/ddp    # Name Binding Protocol\n\
+                    ; indented overlay comment\n\
+            "
+        )
+        .expect("Could not write to temp file");
+
+        assert_eq!(
+            parse_file(temp_path, false),
+            Err(ParseError::MalformedPort("local".to_string()))
+        );
+
+        let opts = ParseOptions {
+            also_treat_semicolon_as_comment: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_file_with_options(temp_path, &opts),
+            Ok(vec!(
+                ServiceEntry {
+                    name: "tcpmux".to_string(),
+                    port: 1,
+                    protocol: "tcp".to_string(),
+                    aliases: vec!(),
+                    ..Default::default()
+                },
+                ServiceEntry {
+                    name: "nbp".to_string(),
+                    port: 2,
+                    protocol: "ddp".to_string(),
+                    aliases: vec!(),
+                    ..Default::default()
+                },
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_file_no_trailing_newline() {
+        let temp_file = Temp::new_file().unwrap();
+        let expected = vec![
+            "tcpmux 1/tcp".parse::<ServiceEntry>().unwrap(),
+            "http 80/tcp".parse().unwrap(),
+        ];
+
+        fs::write(temp_file.as_path(), "tcpmux 1/tcp\nhttp 80/tcp").unwrap();
+        assert_eq!(parse_file(temp_file.as_path(), false), Ok(expected.clone()));
+
+        fs::write(temp_file.as_path(), "tcpmux 1/tcp\nhttp 80/tcp # web").unwrap();
+        assert_eq!(parse_file(temp_file.as_path(), false), Ok(expected.clone()));
+
+        fs::write(temp_file.as_path(), "tcpmux 1/tcp\r\nhttp 80/tcp\r\n# end").unwrap();
+        assert_eq!(parse_file(temp_file.as_path(), false), Ok(expected.clone()));
+
+        fs::write(temp_file.as_path(), "tcpmux 1/tcp\nhttp 80/tcp\n   # end").unwrap();
+        assert_eq!(parse_file(temp_file.as_path(), false), Ok(expected));
+
+        fs::write(temp_file.as_path(), "# only a comment").unwrap();
+        assert_eq!(parse_file(temp_file.as_path(), false), Ok(vec!()));
+    }
+
+    #[test]
+    fn test_parse_loose_file() {
+        let temp_file = Temp::new_file().unwrap();
+        fs::write(
+            temp_file.as_path(),
+            "# loose format\n\nredis 6379 tcp master cache # primary\nhttp 80/tcp\n",
+        )
+        .unwrap();
+        assert_eq!(
+            parse_loose_file(temp_file.as_path()),
+            Ok(vec![
+                "redis 6379/tcp master cache"
+                    .parse::<ServiceEntry>()
+                    .unwrap(),
+                "http 80/tcp".parse().unwrap(),
+            ])
+        );
+
+        fs::write(temp_file.as_path(), "redis 6379\n").unwrap();
+        assert_eq!(
+            parse_loose_file(temp_file.as_path()),
+            Err(ParseError::MissingProtocol("6379".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_dir() {
+        let temp_dir = Temp::new_dir().unwrap();
+        let dir = temp_dir.as_path();
+        fs::write(dir.join("20-local"), "http 8080/tcp\nredis 6379/tcp\n").unwrap();
+        fs::write(dir.join("10-base"), "http 80/tcp\nssh 22/tcp\n").unwrap();
+        fs::write(dir.join("30-extra"), "# nothing yet\n").unwrap();
+        fs::create_dir(dir.join("40-subdir")).unwrap();
+
+        let opts = ParseOptions {
+            lookup_policy: LookupPolicy::LastWins,
+            ..Default::default()
+        };
+        let entries = parse_dir(dir, &opts).unwrap();
+        let ports: Vec<_> = entries.iter().map(|entry| entry.port).collect();
+        assert_eq!(ports, vec!(80, 22, 8080, 6379));
+        let db = ServiceDatabase::with_options(entries, &opts);
+        assert_eq!(db.port_of("http", Some("tcp")), Some(8080));
+
+        let missing = dir.join("services.d");
+        assert_eq!(
+            parse_dir(&missing, &opts),
+            Err(ParseError::FileNotFound { path: missing })
+        );
+    }
+
+    #[test]
+    fn test_parse_dir_merge_markers() {
+        let temp_dir = Temp::new_dir().unwrap();
+        let dir = temp_dir.as_path();
+        fs::write(
+            dir.join("10-base"),
+            "http 80/tcp www\nhttp 80/udp\nssh 22/tcp\n",
+        )
+        .unwrap();
+        fs::write(dir.join("20-overlay"), "+HTTP www2 www # local names\n").unwrap();
+
+        let opts = ParseOptions {
+            allow_merge_markers: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_dir(dir, &opts),
+            Ok(vec!(
+                "http 80/tcp www www2".parse().unwrap(),
+                "http 80/udp www2 www".parse().unwrap(),
+                "ssh 22/tcp".parse().unwrap(),
+            ))
+        );
+
+        fs::write(dir.join("30-overlay"), "+ftp fsp\n").unwrap();
+        assert_eq!(
+            parse_dir(dir, &opts),
+            Err(ParseError::UnknownMergeTarget("ftp".to_string()))
+        );
+        assert_eq!(
+            super::parse_str("+ # comment", &opts),
+            Err(ParseError::MalformedInput("+ # comment".to_string()))
+        );
+        assert_eq!(
+            super::parse_str("+http www2", &ParseOptions::default()),
+            Err(ParseError::MalformedPort("www2".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_file_crlf() {
+        let temp_file = Temp::new_file().unwrap();
+        fs::write(
+            temp_file.as_path(),
+            "# services\r\n\r\ntcpmux 1/tcp\r\nhttp 80/tcp www \t\r\n \r\r\nnbp 2/ddp # Name\r\n",
+        )
+        .unwrap();
+
+        let opts = ParseOptions {
+            keep_comments: true,
+            ..Default::default()
+        };
+        let entries = parse_file_with_options(temp_file.as_path(), &opts).unwrap();
+        assert_eq!(
+            entries,
+            vec!(
+                "tcpmux 1/tcp".parse::<ServiceEntry>().unwrap(),
+                "http 80/tcp www".parse().unwrap(),
+                ServiceEntry {
+                    comment: Some("Name".to_string()),
+                    ..super::parse_entry("nbp 2/ddp", &opts).unwrap()
+                },
+            )
+        );
+        assert_eq!(
+            validate_file_streaming(temp_file.as_path(), &opts),
+            Ok(ParseStats {
+                lines: 6,
+                entries: 3,
+                skipped: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_file_lenient_names() {
+        let temp_file = Temp::new_file().unwrap();
+        fs::write(
+            temp_file.as_path(),
+            "tcpmux 1/tcp\n       2/tcp compressnet\n   3/udp\nhttp 80/tcp\n",
+        )
+        .unwrap();
+        assert_eq!(
+            parse_file_lenient_names(temp_file.as_path()),
             Ok(vec!(
+                "tcpmux 1/tcp".parse().unwrap(),
+                "http 80/tcp".parse().unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_file(temp_file.as_path(), false),
+            Err(ParseError::MissingServiceName("2/tcp".to_string()))
+        );
+
+        fs::write(temp_file.as_path(), "   2/tcp\nhttp asdf/tcp\n").unwrap();
+        assert_eq!(
+            parse_file_lenient_names(temp_file.as_path()),
+            Err(ParseError::MalformedPort("asdf".to_string()))
+        );
+        assert_eq!(
+            "80/tcp http".parse::<ServiceEntry>(),
+            Err(ParseError::MissingServiceName("80/tcp".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_file_with_warnings() {
+        let temp_file = Temp::new_file().unwrap();
+        fs::write(
+            temp_file.as_path(),
+            "tcpmux 1/tcp\nhttp asdf/tcp\n# comment\n   3/udp\nssh 22/tcp\n",
+        )
+        .unwrap();
+        assert_eq!(
+            parse_file_with_warnings(temp_file.as_path()),
+            Ok((
+                vec!(
+                    "tcpmux 1/tcp".parse().unwrap(),
+                    "ssh 22/tcp".parse().unwrap()
+                ),
+                vec!(
+                    (2, ParseError::MalformedPort("asdf".to_string())),
+                    (4, ParseError::MissingServiceName("3/udp".to_string())),
+                )
+            ))
+        );
+
+        let missing = temp_file.as_path().with_extension("missing");
+        assert_eq!(
+            parse_file_with_warnings(&missing),
+            Err(ParseError::FileNotFound { path: missing })
+        );
+    }
+
+    #[test]
+    fn test_parse_file_missing() {
+        let temp_dir = Temp::new_dir().unwrap();
+        let missing = temp_dir.as_path().join("services");
+        assert_eq!(
+            parse_file(&missing, false),
+            Err(ParseError::FileNotFound {
+                path: missing.clone()
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_file_symlink() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = Temp::new_dir().unwrap();
+        let target = temp_dir.as_path().join("services");
+        let link = temp_dir.as_path().join("services.link");
+        let mut file = File::create(&target).unwrap();
+        writeln!(file, "tcpmux 1/tcp").expect("Could not write to temp file");
+        symlink(&target, &link).unwrap();
+
+        assert_eq!(
+            parse_file(&link, false),
+            Ok(vec!(ServiceEntry {
+                name: "tcpmux".to_string(),
+                port: 1,
+                protocol: "tcp".to_string(),
+                aliases: vec!(),
+                ..Default::default()
+            }))
+        );
+
+        fs::remove_file(&target).unwrap();
+        assert_eq!(
+            parse_file(&link, false),
+            Err(ParseError::FileNotFound { path: link.clone() })
+        );
+    }
+
+    #[test]
+    fn test_parse_file_include() {
+        let temp_dir = Temp::new_dir().unwrap();
+        let main = temp_dir.as_path().join("services");
+        fs::create_dir(temp_dir.as_path().join("services.d")).unwrap();
+        let custom = temp_dir.as_path().join("services.d").join("custom");
+
+        let mut file = File::create(&main).unwrap();
+        writeln!(file, "tcpmux 1/tcp\ninclude services.d/custom\nnbp 2/ddp").unwrap();
+        let mut file = File::create(&custom).unwrap();
+        writeln!(file, "# local services\nmyservice 9000/tcp").unwrap();
+
+        assert_eq!(
+            parse_file(&main, false),
+            Err(ParseError::MalformedPort("services.d".to_string()))
+        );
+
+        let opts = ParseOptions {
+            follow_includes: true,
+            ..Default::default()
+        };
+        let names: Vec<_> = parse_file_with_options(&main, &opts)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, vec!("tcpmux", "myservice", "nbp"));
+    }
+
+    #[test]
+    fn test_parse_file_include_service() {
+        let opts = ParseOptions {
+            follow_includes: true,
+            ..Default::default()
+        };
+        let entries = parse_str("include 1234/tcp\ninclude 1234/udp alias\n", &opts).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|entry| entry.name == "include"));
+        assert_eq!(entries[1].aliases, vec!("alias"));
+    }
+
+    #[test]
+    fn test_parse_file_include_depth() {
+        let temp_dir = Temp::new_dir().unwrap();
+        let path = |i: usize| temp_dir.as_path().join(format!("services.{}", i));
+        // services.0 includes services.1 and so on, 17 files in total
+        for i in 0..=16 {
+            let mut file = File::create(path(i)).unwrap();
+            writeln!(file, "service{} {}/tcp", i, 9000 + i).unwrap();
+            if i < 16 {
+                writeln!(file, "include services.{}", i + 1).unwrap();
+            }
+        }
+
+        let opts = ParseOptions {
+            follow_includes: true,
+            ..Default::default()
+        };
+        assert_eq!(parse_file_with_options(&path(1), &opts).unwrap().len(), 16);
+        assert_eq!(
+            parse_file_with_options(&path(0), &opts),
+            Err(ParseError::IncludeDepthExceeded {
+                path: fs::canonicalize(path(16)).unwrap()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_file_include_cycle() {
+        let temp_dir = Temp::new_dir().unwrap();
+        let a = temp_dir.as_path().join("a");
+        let b = temp_dir.as_path().join("b");
+
+        let mut file = File::create(&a).unwrap();
+        writeln!(file, "tcpmux 1/tcp\ninclude b").unwrap();
+        let mut file = File::create(&b).unwrap();
+        writeln!(file, "nbp 2/ddp\ninclude {}", a.display()).unwrap();
+
+        let opts = ParseOptions {
+            follow_includes: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_file_with_options(&a, &opts),
+            Err(ParseError::IncludeCycle {
+                path: fs::canonicalize(&a).unwrap()
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_file_socket() {
+        use std::os::unix::net::UnixListener;
+
+        let temp_dir = Temp::new_dir().unwrap();
+        let socket = temp_dir.as_path().join("services.sock");
+        let _listener = UnixListener::bind(&socket).unwrap();
+        assert_eq!(
+            parse_file(&socket, false),
+            Err(ParseError::NotARegularFile { path: socket })
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_file_non_regular() {
+        let null = std::path::Path::new("/dev/null");
+        assert_eq!(
+            parse_file(null, false),
+            Err(ParseError::NotARegularFile {
+                path: null.to_path_buf()
+            })
+        );
+
+        let opts = ParseOptions {
+            allow_non_regular: true,
+            ..Default::default()
+        };
+        assert_eq!(parse_file_with_options(null, &opts), Ok(vec!()));
+
+        let temp_dir = Temp::new_dir().unwrap();
+        assert_eq!(
+            parse_file_with_options(temp_dir.as_path(), &opts),
+            Err(ParseError::NotARegularFile {
+                path: temp_dir.as_path().to_path_buf()
+            })
+        );
+    }
+
+    #[test]
+    fn test_reserve_hint() {
+        let opts = ParseOptions::default();
+        assert_eq!(reserve_hint(0, &opts), 0);
+        assert_eq!(reserve_hint(4800, &opts), 100);
+        assert_eq!(reserve_hint(1 << 30, &opts), 1 << 16);
+        assert_eq!(reserve_hint(u64::MAX, &opts), 1 << 16);
+
+        let opts = ParseOptions {
+            max_total_entries: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(reserve_hint(4800, &opts), 10);
+        assert_eq!(reserve_hint(48, &opts), 1);
+    }
+
+    #[test]
+    fn test_parse_file_reserved() {
+        let temp_file = Temp::new_file().unwrap();
+        let temp_path = temp_file.as_path();
+        let mut file = File::create(temp_path).unwrap();
+
+        // Excerpt of the IANA port-numbers registry, the placeholder rows have a blank name
+        write!(
+            file,
+            "\
+                                   0/tcp    Reserved\n\
+                                   0/udp    Reserved\n\
+                #                          Jon Postel <postel@isi.edu>\n\
+                tcpmux             1/tcp    # TCP Port Service Multiplexer\n\
+                tcpmux             1/udp    # TCP Port Service Multiplexer\n\
+                                   4/tcp    Unassigned\n\
+                                   4/udp    Unassigned\n\
+            "
+        )
+        .expect("Could not write to temp file");
+
+        assert_eq!(
+            parse_file(temp_path, false),
+            Err(ParseError::MissingServiceName("0/tcp".to_string()))
+        );
+
+        let opts = ParseOptions {
+            capture_reserved: true,
+            ..Default::default()
+        };
+        let (entries, reserved) = parse_file_with_reserved(temp_path, &opts).unwrap();
+        assert_eq!(
+            entries,
+            vec!(
                 ServiceEntry {
-                    name: "rtmp".to_string(),
+                    name: "tcpmux".to_string(),
                     port: 1,
-                    protocol: "ddp".to_string(),
+                    protocol: "tcp".to_string(),
                     aliases: vec!(),
+                    ..Default::default()
                 },
                 ServiceEntry {
                     name: "tcpmux".to_string(),
                     port: 1,
                     protocol: "udp".to_string(),
                     aliases: vec!(),
+                    ..Default::default()
                 },
-                ServiceEntry {
-                    name: "tcpmux".to_string(),
-                    port: 1,
+            )
+        );
+        assert_eq!(
+            reserved,
+            vec!(
+                ReservedRange {
+                    port: 0,
                     protocol: "tcp".to_string(),
-                    aliases: vec!(),
-                },
-                ServiceEntry {
-                    name: "nbp".to_string(),
-                    port: 2,
-                    protocol: "ddp".to_string(),
-                    aliases: vec!(),
                 },
-                ServiceEntry {
-                    name: "compressnet".to_string(),
-                    port: 2,
+                ReservedRange {
+                    port: 0,
                     protocol: "udp".to_string(),
-                    aliases: vec!(),
                 },
-                ServiceEntry {
-                    name: "compressnet".to_string(),
-                    port: 2,
+                ReservedRange {
+                    port: 4,
                     protocol: "tcp".to_string(),
-                    aliases: vec!(),
                 },
-                ServiceEntry {
-                    name: "compressnet".to_string(),
-                    port: 3,
+                ReservedRange {
+                    port: 4,
                     protocol: "udp".to_string(),
-                    aliases: vec!(),
                 },
-                ServiceEntry {
-                    name: "compressnet".to_string(),
-                    port: 3,
-                    protocol: "tcp".to_string(),
-                    aliases: vec!(),
+            )
+        );
+
+        assert_eq!(parse_file_with_options(temp_path, &opts).unwrap(), entries);
+    }
+
+    #[test]
+    fn test_report() {
+        let temp_file = Temp::new_file().unwrap();
+        fs::write(
+            temp_file.as_path(),
+            "# comment\ntcpmux 1/tcp\nhttp asdf/tcp\n\n9p 564/tcp plan9 9P\ngopher\n",
+        )
+        .unwrap();
+
+        let report = super::report(temp_file.as_path(), &ParseOptions::default());
+        assert_eq!(
+            report,
+            ServicesReport {
+                stats: ParseStats {
+                    lines: 6,
+                    entries: 2,
+                    skipped: 2,
                 },
+                entries: vec!(
+                    "tcpmux 1/tcp".parse().unwrap(),
+                    "9p 564/tcp plan9 9P".parse().unwrap()
+                ),
+                problems: vec!(
+                    (1, EntryProblem::NameStartsWithDigit),
+                    (1, EntryProblem::AliasIsName("9P".to_string())),
+                ),
+                errors: vec!(
+                    ParseErrorAt {
+                        line: 3,
+                        error: ParseError::MalformedPort("asdf".to_string())
+                    },
+                    ParseErrorAt {
+                        line: 6,
+                        error: ParseError::MissingPortProtocol("gopher".to_string())
+                    },
+                ),
+                conflicts: vec!(),
+                alias_collisions: vec!(),
+                name_port_anomalies: vec!(),
+                overlong_names: vec!(),
+            }
+        );
+        assert!(!report.is_clean());
+
+        fs::write(temp_file.as_path(), "tcpmux 1/tcp\n").unwrap();
+        assert!(super::report(temp_file.as_path(), &ParseOptions::default()).is_clean());
+
+        let missing = temp_file.as_path().with_extension("missing");
+        let report = super::report(&missing, &ParseOptions::default());
+        assert_eq!(
+            report.errors,
+            vec!(ParseErrorAt {
+                line: 0,
+                error: ParseError::FileNotFound { path: missing }
+            })
+        );
+    }
+
+    #[test]
+    fn test_report_database_checks() {
+        let temp_file = Temp::new_file().unwrap();
+        let long_name = "a".repeat(ServiceDatabase::DEFAULT_MAX_NAME_LEN + 1);
+        fs::write(
+            temp_file.as_path(),
+            format!(
+                "http 80/tcp www\nwww 8080/tcp\nhttp 8080/udp\n{} 9000/tcp\n",
+                long_name
+            ),
+        )
+        .unwrap();
+
+        let report = super::report(temp_file.as_path(), &ParseOptions::default());
+        assert!(report.problems.is_empty() && report.errors.is_empty());
+        assert_eq!(
+            report.conflicts,
+            vec!(Conflict::AliasIsOtherName {
+                name: "http".to_string(),
+                alias: "www".to_string(),
+                other_port: 8080,
+            })
+        );
+        assert_eq!(
+            report.alias_collisions,
+            vec!((
+                "www".to_string(),
+                vec!(report.entries[0].clone(), report.entries[1].clone())
             ))
         );
+        assert_eq!(
+            report.name_port_anomalies,
+            vec!(("http".to_string(), vec!(80, 8080)))
+        );
+        assert_eq!(report.overlong_names, vec!(report.entries[3].clone()));
+        assert!(!report.is_clean());
     }
 
     #[test]
-    fn test_parse_file_errors() {
+    fn test_validate_file_streaming() {
         let temp_file = Temp::new_file().unwrap();
-        let temp_path = temp_file.as_path();
-        let mut file = File::create(temp_path).unwrap();
+        let mut file = File::create(temp_file.as_path()).unwrap();
+        writeln!(file, "# comment").unwrap();
+        writeln!(file, "tcpmux 1/tcp").unwrap();
+        writeln!(file, "http asdf/tcp").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, "gopher").unwrap();
+        writeln!(file, "nbp 2/ddp").unwrap();
 
-        write!(file, "service\n").expect("");
+        let opts = ParseOptions {
+            ignore_errs: true,
+            ..Default::default()
+        };
+        let errors = validate_file_streaming(temp_file.as_path(), &opts).unwrap_err();
         assert_eq!(
-            parse_file(&temp_path, false),
-            Err("Could not find port and protocol field")
+            errors,
+            vec!(
+                ParseErrorAt {
+                    line: 3,
+                    error: ParseError::MalformedPort("asdf".to_string())
+                },
+                ParseErrorAt {
+                    line: 5,
+                    error: ParseError::MissingPortProtocol("gopher".to_string())
+                },
+            )
         );
+        assert_eq!(errors[0].to_string(), "line 3: malformed port \"asdf\"");
 
-        file.set_len(0).expect("");
-        file.seek(SeekFrom::Start(0)).expect("");
-        write!(file, "service # 1/tcp\n").expect("");
+        let mut file = File::create(temp_file.as_path()).unwrap();
+        writeln!(file, "# comment\ntcpmux 1/tcp\n\nnbp 2/ddp").unwrap();
         assert_eq!(
-            parse_file(&temp_path, false),
-            Err("Could not find port and protocol field")
+            validate_file_streaming(temp_file.as_path(), &ParseOptions::default()),
+            Ok(ParseStats {
+                lines: 4,
+                entries: 2,
+                skipped: 2
+            })
         );
 
-        file.set_len(0).expect("");
-        file.seek(SeekFrom::Start(0)).expect("");
-        write!(file, "service  1#/tcp\n").expect("");
-        assert_eq!(parse_file(&temp_path, false), Err("Malformed port"));
+        let opts = ParseOptions {
+            max_total_entries: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_file_streaming(temp_file.as_path(), &opts),
+            Err(vec!(ParseErrorAt {
+                line: 4,
+                error: ParseError::TooManyEntries(1)
+            }))
+        );
+        assert_eq!(
+            super::report(temp_file.as_path(), &opts).errors,
+            vec!(ParseErrorAt {
+                line: 4,
+                error: ParseError::TooManyEntries(1)
+            })
+        );
 
-        file.set_len(0).expect("");
-        file.seek(SeekFrom::Start(0)).expect("");
-        write!(file, "service  1/#tcp\n").expect("");
+        let missing = temp_file.as_path().with_extension("missing");
+        assert_eq!(
+            validate_file_streaming(&missing, &ParseOptions::default()),
+            Err(vec!(ParseErrorAt {
+                line: 0,
+                error: ParseError::FileNotFound { path: missing }
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_reader_keep_raw() {
+        let contents = "  tcpmux\t1/tcp    mux  # multiplexer\nnbp 2/ddp\n";
+
+        let entries = parse_reader(contents.as_bytes(), &ParseOptions::default()).unwrap();
+        assert!(entries.iter().all(|entry| entry.raw.is_none()));
+
+        let opts = ParseOptions {
+            keep_raw: true,
+            ..Default::default()
+        };
+        let entries = parse_reader(contents.as_bytes(), &opts).unwrap();
         assert_eq!(
-            parse_file(&temp_path, false),
-            Err("Could not find protocol")
+            entries[0].raw.as_deref(),
+            Some("  tcpmux\t1/tcp    mux  # multiplexer")
         );
+        assert_eq!(entries[0].aliases, vec!("mux".to_string()));
+        assert_eq!(entries[1].raw.as_deref(), Some("nbp 2/ddp"));
+    }
 
-        file.set_len(0).expect("");
-        file.seek(SeekFrom::Start(0)).expect("");
-        write!(file, "service asdf/tcp\n").expect("");
-        assert_eq!(parse_file(&temp_path, false), Err("Malformed port"));
+    #[test]
+    fn test_parse_reader_cursor() {
+        // parse_stdin reads through parse_reader, which is tested here with an in-memory reader
+        let cursor = io::Cursor::new(b"tcpmux 1/tcp\nhttp 80/tcp www\n".to_vec());
+        let entries = parse_reader(cursor, &ParseOptions::default()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].aliases, vec!("www".to_string()));
+    }
 
-        file.set_len(0).expect("");
-        file.seek(SeekFrom::Start(0)).expect("");
-        write!(file, "service asdf/\n").expect("");
-        assert_eq!(parse_file(&temp_path, false), Err("Malformed port"));
+    #[test]
+    fn test_parse_reader_invalid_utf8() {
+        let contents = b"tcpmux 1/tcp\nbad\xff 2/tcp\nnbp 2/ddp\n";
+
+        let err = parse_reader(&contents[..], &ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Io(io::Error::from(io::ErrorKind::InvalidData))
+        );
+
+        let opts = ParseOptions {
+            ignore_errs: true,
+            ..Default::default()
+        };
+        let entries = parse_reader(&contents[..], &opts).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].name, "nbp");
+
+        let temp_file = Temp::new_file().unwrap();
+        fs::write(temp_file.as_path(), &contents[..]).unwrap();
+        let errors = validate_file_streaming(temp_file.as_path(), &opts).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 2);
+    }
+
+    #[test]
+    fn test_parse_appended() {
+        let mut contents = "# services\ntcpmux 1/tcp\nhttp 80/tcp\n".to_string();
+        let entries = parse_appended(io::Cursor::new(&contents), 0).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        contents.push_str("ssh 22/tcp\n# more\nsmtp 25/tcp\n");
+        let entries = parse_appended(io::Cursor::new(&contents), 3).unwrap();
+        let names: Vec<_> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!("ssh", "smtp"));
+
+        assert_eq!(parse_appended(io::Cursor::new(&contents), 10), Ok(vec!()));
+    }
+
+    #[test]
+    fn test_find_in_reader() {
+        let contents = "\
+            # services\n\
+            tcpmux 1/tcp\n\
+            nbp 2/ddp\n\
+            http 80/tcp www\n\
+            ssh 22/tcp\n\
+            bad\n\
+            ";
+        let mut cursor = io::Cursor::new(contents);
+
+        let entry = find_in_reader(&mut cursor, |entry| entry.port == 80).unwrap();
+        assert_eq!(entry.unwrap().name, "http");
+        // nothing after the match was read
+        assert_eq!(cursor.position() as usize, contents.find("ssh").unwrap());
+
+        let rest = parse_reader(&mut cursor, &ParseOptions::default());
+        assert_eq!(
+            rest,
+            Err(ParseError::MissingPortProtocol("bad".to_string()))
+        );
+
+        cursor.set_position(0);
+        let entry = find_in_reader(&mut cursor, |entry| entry.name == "smtp").unwrap();
+        assert_eq!(entry, None);
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn test_parse_reader_encoding() {
+        let contents = b"cafe 8080/tcp # Caf\xe9 cr\xe8me\nhttp 80/tcp\n";
+        let opts = ParseOptions {
+            keep_comments: true,
+            encoding: encoding_rs::WINDOWS_1252,
+            ..Default::default()
+        };
+        let entries = parse_reader(&contents[..], &opts).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].comment, Some("Café crème".to_string()));
+
+        assert_eq!(
+            parse_reader(&contents[..], &ParseOptions::default()),
+            Err(ParseError::Io(io::Error::from(io::ErrorKind::InvalidData)))
+        );
+    }
+
+    #[test]
+    fn test_parse_reader_merge_definitions() {
+        let contents = "\
+            http 80/tcp www\n\
+            http 80/udp www\n\
+            HTTP 80/TCP www-http www\n\
+            http 8080/tcp\n\
+            ";
+        let opts = ParseOptions {
+            merge_duplicate_definitions: true,
+            ..Default::default()
+        };
+        let entries = parse_reader(contents.as_bytes(), &opts).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0], "http 80/tcp www www-http");
+        assert_eq!(entries[1], "http 80/udp www");
+        assert_eq!(entries[2], "http 8080/tcp");
+
+        let entries = parse_reader(contents.as_bytes(), &ParseOptions::default()).unwrap();
+        assert_eq!(entries.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_reader_spans() {
+        let contents = "# services\ntcpmux 1/tcp\r\n  http 80/tcp www # web\n\nnbp 2/ddp";
+        let opts = ParseOptions {
+            track_spans: true,
+            ..Default::default()
+        };
+        let entries = parse_reader(contents.as_bytes(), &opts).unwrap();
+
+        let spans: Vec<_> = entries
+            .iter()
+            .map(|entry| entry.span.clone().unwrap())
+            .collect();
+        assert_eq!(spans, vec!(11..23, 27..48, 50..59));
+        assert_eq!(&contents[spans[1].clone()], "http 80/tcp www # web");
+        assert_eq!(&contents[spans[2].clone()], "nbp 2/ddp");
+
+        let entries = parse_reader(contents.as_bytes(), &ParseOptions::default()).unwrap();
+        assert!(entries.iter().all(|entry| entry.span.is_none()));
+    }
+
+    #[test]
+    fn test_parse_reader_disabled() {
+        let contents = "# services\n#http 80/tcp www # web\n  # ssh 22/tcp\nsmtp 25/tcp\n#\n";
+        let opts = ParseOptions {
+            parse_disabled: true,
+            ..Default::default()
+        };
+        let entries = parse_reader(contents.as_bytes(), &opts).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0],
+            ServiceEntry {
+                disabled: true,
+                ..super::parse_entry("http 80/tcp www", &opts).unwrap()
+            }
+        );
+        assert!(entries[1].disabled);
+        assert_eq!(entries[1].name, "ssh");
+        assert!(!entries[2].disabled);
+
+        let entries = parse_reader(contents.as_bytes(), &ParseOptions::default()).unwrap();
+        assert_eq!(entries.len(), 1);
+
+        assert_eq!(
+            "".parse::<ServiceEntry>(),
+            Err(ParseError::MalformedInput("".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_reader_comma_protocols() {
+        let contents = "myproto 9000/tcp,UDP mine # comment\nhttp 80/tcp\n";
+        let opts = ParseOptions {
+            allow_comma_protocols: true,
+            ..Default::default()
+        };
+        let entries = parse_reader(contents.as_bytes(), &opts).unwrap();
+        assert_eq!(
+            entries,
+            vec!(
+                super::parse_entry("myproto 9000/tcp mine", &opts).unwrap(),
+                super::parse_entry("myproto 9000/udp mine", &opts).unwrap(),
+                super::parse_entry("http 80/tcp", &opts).unwrap(),
+            )
+        );
+        assert_eq!(
+            super::parse_entry("myproto 9000/tcp,", &opts),
+            Err(ParseError::MissingProtocol("9000/".to_string()))
+        );
+
+        let entries = parse_reader(contents.as_bytes(), &ParseOptions::default()).unwrap();
+        assert_eq!(entries[0].protocol, "tcp,udp");
+    }
+
+    #[test]
+    fn test_parse_reader_line_continuations() {
+        let contents = "# a long \\\ncomment\nhttp 80/tcp \\\nwww \\\nwww-http # web\nbad \\\n";
+        let opts = ParseOptions {
+            line_continuations: true,
+            ignore_errs: true,
+            ..Default::default()
+        };
+        let expected = vec![super::parse_entry("http 80/tcp www www-http", &opts).unwrap()];
+        assert_eq!(
+            parse_reader(contents.as_bytes(), &opts),
+            Ok(expected.clone())
+        );
+        assert_eq!(super::parse_str(contents, &opts), Ok(expected.clone()));
+        let entries: Result<Vec<_>, _> = parse_reader_iter(contents.as_bytes(), &opts).collect();
+        assert_eq!(entries, Ok(expected));
+
+        let opts = ParseOptions {
+            line_continuations: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            super::parse_str(contents, &opts),
+            Err(ParseError::MissingPortProtocol("bad".to_string()))
+        );
+        let unspaced = "http 80/tcp\\\nwww\nssh\\\n22/tcp\n";
+        assert_eq!(
+            super::parse_str(unspaced, &opts),
+            Ok(vec!(
+                "http 80/tcp www".parse().unwrap(),
+                "ssh 22/tcp".parse().unwrap()
+            ))
+        );
+        let opts_with_spans = ParseOptions {
+            track_spans: true,
+            ..opts.clone()
+        };
+        let spans: Vec<_> = super::parse_str(unspaced, &opts_with_spans)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.span.unwrap())
+            .collect();
+        assert_eq!(spans, vec!(0..16, 17..28));
+        assert_eq!(&unspaced[spans[1].clone()], "ssh\\\n22/tcp");
+        let results: Vec<_> = parse_reader_iter("ssh \\\n22/tcp\nbad".as_bytes(), &opts).collect();
+        assert_eq!(
+            results,
+            vec!(
+                Ok("ssh 22/tcp".parse().unwrap()),
+                Err(ParseError::MissingPortProtocol("bad".to_string())),
+            )
+        );
+
+        assert_eq!(
+            parse_reader(contents.as_bytes(), &ParseOptions::default()),
+            Err(ParseError::MissingPortProtocol("comment".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_tsv() {
+        let contents =
+            "# name\tport\taliases\nhttp\t80/tcp\t\t# web\n\nsmtp\t25/tcp\tmail\r\nssh\t22/tcp\n";
+        assert_eq!(
+            parse_tsv(contents.as_bytes()),
+            Ok(vec!(
+                "http 80/tcp".parse().unwrap(),
+                "smtp 25/tcp mail".parse().unwrap(),
+                "ssh 22/tcp".parse().unwrap(),
+            ))
+        );
+
+        let aliases = |line: &str| parse_tsv(line.as_bytes()).unwrap()[0].aliases.clone();
+        assert_eq!(aliases("http\t80/tcp\t# web\n"), Vec::<String>::new());
+        assert_eq!(aliases("http\t80/tcp\twww #web\n"), vec!("www"));
+        assert_eq!(aliases("http\t80/tcp\twww#web www-http\n"), vec!("www"));
+        assert_eq!(aliases("http\t80/tcp\twww # web\tmore\n"), vec!("www"));
+
+        let contents = "\
+            \t80/tcp\n\
+            http\t\twww\n\
+            ssh\t22/tcp\n\
+            http\t80 tcp\n\
+            http\t80/tcp\twww\textra\n\
+            ";
+        assert_eq!(
+            parse_tsv(contents.as_bytes()),
+            Err(ParseError::MalformedInput("".to_string()))
+        );
+        assert_eq!(
+            parse_tsv(&contents.as_bytes()[8..]),
+            Err(ParseError::MissingPortProtocol("http".to_string()))
+        );
+        assert_eq!(
+            parse_tsv_with_warnings(contents.as_bytes()),
+            Ok((
+                vec!("ssh 22/tcp".parse().unwrap()),
+                vec!(
+                    (1, ParseError::MalformedInput("".to_string())),
+                    (2, ParseError::MissingPortProtocol("http".to_string())),
+                    (4, ParseError::MalformedPort("80 tcp".to_string())),
+                    (5, ParseError::MalformedInput("extra".to_string())),
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_reader_iter() {
+        let contents = "# services\nhttp 80/tcp\nbad\nssh 22/tcp\n";
+        let results: Vec<_> = parse_reader_iter(contents.as_bytes(), &ParseOptions::default())
+            .map(|result| result.map(|entry| entry.name))
+            .collect();
+        assert_eq!(
+            results,
+            vec!(
+                Ok("http".to_string()),
+                Err(ParseError::MissingPortProtocol("bad".to_string())),
+                Ok("ssh".to_string()),
+            )
+        );
+
+        let opts = ParseOptions {
+            ignore_errs: true,
+            ..Default::default()
+        };
+        assert_eq!(parse_reader_iter(contents.as_bytes(), &opts).count(), 2);
+    }
+
+    #[test]
+    fn test_parse_str() {
+        let contents = "# services\ntcpmux 1/tcp\r\n  http 80/tcp www # web\n\nnbp 2/ddp";
+        let opts = ParseOptions {
+            track_spans: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            super::parse_str(contents, &opts),
+            parse_reader(contents.as_bytes(), &opts)
+        );
+
+        assert_eq!(
+            super::parse_str("http 80/tcp\nhttp /tcp\n", &ParseOptions::default()),
+            Err(ParseError::MalformedPort("".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_str_max_total_entries() {
+        let contents = "tcpmux 1/tcp\n# comment\nhttp 80/tcp\nhttp asdf/udp\nssh 22/tcp\n";
+        let opts = ParseOptions {
+            ignore_errs: true,
+            max_total_entries: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            super::parse_str(contents, &opts),
+            Err(ParseError::TooManyEntries(2))
+        );
+        assert_eq!(
+            parse_reader(contents.as_bytes(), &opts),
+            Err(ParseError::TooManyEntries(2))
+        );
+        let mut entries = parse_reader_iter(contents.as_bytes(), &opts);
+        assert_eq!(entries.next().unwrap().unwrap().name, "tcpmux");
+        assert_eq!(entries.next().unwrap().unwrap().name, "http");
+        assert_eq!(entries.next(), Some(Err(ParseError::TooManyEntries(2))));
+        assert_eq!(entries.next(), None);
+
+        let opts = ParseOptions {
+            max_total_entries: Some(3),
+            ..opts
+        };
+        assert_eq!(super::parse_str(contents, &opts).unwrap().len(), 3);
+
+        let opts = ParseOptions {
+            allow_comma_protocols: true,
+            max_total_entries: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            super::parse_str("myproto 9000/tcp,udp\n", &opts),
+            Err(ParseError::TooManyEntries(1))
+        );
+    }
+
+    #[test]
+    fn test_parse_reader_progress() {
+        let contents = "\
+            # WELL KNOWN PORT NUMBERS\n\
+            rtmp              1/ddp    #Routing Table Maintenance Protocol\n\
+            tcpmux            1/udp     # TCP Port Service Multiplexer\n\
+            tcpmux            1/tcp     # TCP Port Service Multiplexer\n\
+            nbp               2/ddp    #Name Binding Protocol\n\
+            compressnet       2/udp     # Management Utility\n\
+            compressnet       2/tcp     # Management Utility\n\
+        ";
+        let opts = ParseOptions {
+            progress_interval: 2,
+            ..Default::default()
+        };
+
+        let mut progress = Vec::new();
+        let entries =
+            parse_reader_with_progress(contents.as_bytes(), &opts, |line| progress.push(line))
+                .unwrap();
+        assert_eq!(entries.len(), 6);
+        assert_eq!(progress, vec!(2, 4, 6));
 
+        assert_eq!(parse_reader(contents.as_bytes(), &opts), Ok(entries));
+
+        let opts = ParseOptions {
+            progress_interval: 0,
+            ..Default::default()
+        };
+        let mut calls = 0;
+        parse_reader_with_progress(contents.as_bytes(), &opts, |_| calls += 1).unwrap();
+        assert_eq!(calls, 0);
+    }
+
+    #[test]
+    fn test_parse_file_or_empty() {
         let temp_dir = Temp::new_dir().unwrap();
-        let temp_dir_path = temp_dir.as_path();
+        let missing = temp_dir.as_path().join("services");
+        assert_eq!(parse_file_or_empty(&missing, false), Ok(vec!()));
+
+        let mut file = File::create(&missing).unwrap();
+        writeln!(file, "tcpmux 1/tcp").unwrap();
+        assert_eq!(parse_file_or_empty(&missing, false).unwrap().len(), 1);
+
         assert_eq!(
-            parse_file(&temp_dir_path, false),
-            Err("File does not exist or is not a regular file")
+            parse_file_or_empty(temp_dir.as_path(), false),
+            Err(ParseError::NotARegularFile {
+                path: temp_dir.as_path().to_path_buf()
+            })
         );
     }
 
     #[test]
     fn test_parse_servicefile() {
-        assert_eq!(parse_servicefile(true).is_ok(), true);
+        assert!(parse_servicefile(true).is_ok());
+    }
+
+    #[test]
+    fn test_parse_servicefile_or_empty() {
+        use crate::file::parse_system_file_or_empty;
+
+        let temp_dir = Temp::new_dir().unwrap();
+        let missing = temp_dir.as_path().join("services");
+        assert!(parse_system_file_or_empty(&missing, false).is_empty());
+
+        let mut file = File::create(&missing).unwrap();
+        writeln!(file, "tcpmux 1/tcp\nhttp asdf/tcp\nssh 22/tcp").unwrap();
+        assert_eq!(parse_system_file_or_empty(&missing, false).len(), 2);
+        assert_eq!(parse_system_file_or_empty(&missing, true).len(), 2);
+
+        assert!(parse_system_file_or_empty(temp_dir.as_path(), false).is_empty());
     }
 }
@@ -0,0 +1,149 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{ServiceEntry, ServiceResolver};
+
+// The `getserv*` functions return pointers into buffers shared by every thread, and
+// `getservent` keeps a cursor into the database, so each use of them holds this lock until the
+// results have been copied
+static NSS_LOCK: Mutex<()> = Mutex::new(());
+
+fn lock() -> MutexGuard<'static, ()> {
+    // the lock guards no data, so a panic while it was held leaves nothing inconsistent
+    NSS_LOCK.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// Copies a C string, replacing invalid UTF-8
+unsafe fn string(s: *const c_char) -> String {
+    CStr::from_ptr(s).to_string_lossy().into_owned()
+}
+
+// Copies a `servent` returned by libc, which is only valid until the next call into libc
+unsafe fn entry(servent: *const libc::servent) -> ServiceEntry {
+    let servent = &*servent;
+
+    let mut aliases = Vec::new();
+    let mut alias = servent.s_aliases;
+    while !alias.is_null() && !(*alias).is_null() {
+        aliases.push(string(*alias));
+        alias = alias.add(1);
+    }
+
+    ServiceEntry {
+        name: string(servent.s_name),
+        // s_port holds the port in network byte order
+        port: u16::from_be(servent.s_port as u16),
+        protocol: string(servent.s_proto),
+        aliases,
+        ..Default::default()
+    }
+}
+
+/// Read every service known to the system's name service switch, with `getservent`
+/// Depending on `/etc/nsswitch.conf` this can include services from LDAP or other sources as well
+/// as /etc/services, so the results may differ from parsing /etc/services directly. Only the name,
+/// port, protocol and aliases of each entry are set.
+///
+/// Calls into libc from this crate are serialized, so this is safe to use from several threads,
+/// but other code calling the `getserv*` functions directly can still interfere with it.
+pub fn system_services_via_nss() -> Vec<ServiceEntry> {
+    let mut entries = Vec::new();
+    let _guard = lock();
+    unsafe {
+        libc::setservent(0);
+        loop {
+            let servent = libc::getservent();
+            if servent.is_null() {
+                break;
+            }
+            entries.push(entry(servent));
+        }
+        libc::endservent();
+    }
+    entries
+}
+
+/// Looks up services with `getservbyname` and `getservbyport`, serialized with the other calls
+/// into libc like `system_services_via_nss`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NssResolver;
+
+impl ServiceResolver for NssResolver {
+    fn lookup_name(&self, name: &str, proto: Option<&str>) -> Option<ServiceEntry> {
+        let name = CString::new(name).ok()?;
+        let proto = proto.map(CString::new).transpose().ok()?;
+        let proto = proto
+            .as_ref()
+            .map_or(std::ptr::null(), |proto| proto.as_ptr());
+        let _guard = lock();
+        unsafe {
+            let servent = libc::getservbyname(name.as_ptr(), proto);
+            if servent.is_null() {
+                None
+            } else {
+                Some(entry(servent))
+            }
+        }
+    }
+
+    fn lookup_port(&self, port: u16, proto: Option<&str>) -> Option<ServiceEntry> {
+        let proto = proto.map(CString::new).transpose().ok()?;
+        let proto = proto
+            .as_ref()
+            .map_or(std::ptr::null(), |proto| proto.as_ptr());
+        let _guard = lock();
+        unsafe {
+            let servent = libc::getservbyport(port.to_be() as libc::c_int, proto);
+            if servent.is_null() {
+                None
+            } else {
+                Some(entry(servent))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_services() {
+        // minimal containers may have no services database at all
+        let entries = system_services_via_nss();
+        assert!(entries.iter().all(|entry| !entry.name.is_empty()));
+    }
+
+    #[test]
+    fn resolver() {
+        let resolver = NssResolver;
+        // only check a service the host knows about
+        if let Some(entry) = resolver.lookup_name("ssh", Some("tcp")) {
+            assert_eq!(entry.port, 22);
+            assert_eq!(entry.protocol, "tcp");
+            assert_eq!(resolver.lookup_port(22, Some("tcp")).unwrap().name, "ssh");
+        }
+
+        assert_eq!(resolver.lookup_name("no-such-service", None), None);
+        assert_eq!(resolver.lookup_name("ss\0h", None), None);
+    }
+
+    #[test]
+    fn concurrent_lookups() {
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..50 {
+                        let by_name = NssResolver.lookup_name("ssh", Some("tcp"));
+                        let by_port = NssResolver.lookup_port(22, Some("tcp"));
+                        assert_eq!(by_name.is_some(), by_port.is_some());
+                    }
+                    system_services_via_nss().len()
+                })
+            })
+            .collect();
+        let counts: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        assert!(counts.windows(2).all(|pair| pair[0] == pair[1]));
+    }
+}
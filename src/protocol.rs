@@ -0,0 +1,170 @@
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+use core::convert::Infallible;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::ServiceEntry;
+
+/// A transport protocol from the protocol field of a services file
+/// Parsing is case-insensitive, and unknown protocols are kept lowercased in `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+    Sctp,
+    Dccp,
+    Ddp,
+    Other(String),
+}
+
+impl Protocol {
+    /// The lowercase name of the protocol, as written in /etc/services
+    pub fn as_str(&self) -> &str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+            Protocol::Sctp => "sctp",
+            Protocol::Dccp => "dccp",
+            Protocol::Ddp => "ddp",
+            Protocol::Other(name) => name,
+        }
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase();
+        Ok(match name.as_str() {
+            "tcp" => Protocol::Tcp,
+            "udp" => Protocol::Udp,
+            "sctp" => Protocol::Sctp,
+            "dccp" => Protocol::Dccp,
+            "ddp" => Protocol::Ddp,
+            _ => Protocol::Other(name),
+        })
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl AsRef<str> for Protocol {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// Case-insensitive, like `FromStr`, so `Protocol::Tcp == *"TCP"`
+impl PartialEq<str> for Protocol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str().eq_ignore_ascii_case(other)
+    }
+}
+
+impl PartialEq<&str> for Protocol {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+/// A transport which can be used with the sockets of the standard library, see `SocketService`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+/// The port and transport of an entry, see `ServiceEntry::try_into_socket_service`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SocketService {
+    pub port: u16,
+    pub transport: Transport,
+}
+
+impl ServiceEntry {
+    /// Whether the entry's protocol is one of the transports usable with modern sockets, TCP, UDP,
+    /// SCTP or DCCP, e.g. to skip legacy entries like `rtmp 1/ddp`
+    pub fn is_standard_transport(&self) -> bool {
+        matches!(
+            self.protocol.parse(),
+            Ok(Protocol::Tcp | Protocol::Udp | Protocol::Sctp | Protocol::Dccp)
+        )
+    }
+
+    /// The port and transport of a TCP or UDP entry, for networking code which can only use those
+    /// Any other protocol is returned as the error, e.g. `Protocol::Ddp` for `rtmp 1/ddp`.
+    pub fn try_into_socket_service(&self) -> Result<SocketService, Protocol> {
+        let transport = match self.protocol.parse() {
+            Ok(Protocol::Tcp) => Transport::Tcp,
+            Ok(Protocol::Udp) => Transport::Udp,
+            Ok(protocol) => return Err(protocol),
+            Err(infallible) => match infallible {},
+        };
+        Ok(SocketService {
+            port: self.port,
+            transport,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_protocol() {
+        assert_eq!("tcp".parse(), Ok(Protocol::Tcp));
+        assert_eq!("UDP".parse(), Ok(Protocol::Udp));
+        assert_eq!("Foo".parse(), Ok(Protocol::Other("foo".to_string())));
+        assert_eq!(Protocol::Sctp.to_string(), "sctp");
+    }
+
+    #[test]
+    fn compare_to_str() {
+        assert_eq!(Protocol::Tcp, "tcp");
+        assert_eq!(Protocol::Tcp, "TCP");
+        assert!(Protocol::Tcp == *"Tcp");
+        assert_ne!(Protocol::Tcp, "udp");
+        assert_eq!(Protocol::Other("foo".to_string()), "FOO");
+        assert_eq!(Protocol::Udp.as_ref(), "udp");
+    }
+
+    #[test]
+    fn standard_transport() {
+        let entry: ServiceEntry = "rtmp 1/ddp".parse().unwrap();
+        assert!(!entry.is_standard_transport());
+        let entry: ServiceEntry = "http 80/tcp".parse().unwrap();
+        assert!(entry.is_standard_transport());
+        assert!(entry.with_protocol("SCTP").is_standard_transport());
+    }
+
+    #[test]
+    fn socket_service() {
+        let entry: ServiceEntry = "domain 53/UDP".parse().unwrap();
+        assert_eq!(
+            entry.try_into_socket_service(),
+            Ok(SocketService {
+                port: 53,
+                transport: Transport::Udp,
+            })
+        );
+        let entry: ServiceEntry = "http 80/tcp".parse().unwrap();
+        assert_eq!(
+            entry
+                .try_into_socket_service()
+                .map(|service| service.transport),
+            Ok(Transport::Tcp)
+        );
+
+        let entry: ServiceEntry = "rtmp 1/ddp".parse().unwrap();
+        assert_eq!(entry.try_into_socket_service(), Err(Protocol::Ddp));
+        let entry = entry.with_protocol("sctp");
+        assert_eq!(entry.try_into_socket_service(), Err(Protocol::Sctp));
+    }
+}
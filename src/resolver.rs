@@ -0,0 +1,76 @@
+use crate::{ServiceDatabase, ServiceEntry};
+
+/// A source of service entries, e.g. a parsed services file or a built-in table
+/// Code which only needs lookups can depend on this instead of a concrete type.
+pub trait ServiceResolver {
+    /// Find the service called `name`, either by its canonical name or an alias
+    /// If `proto` is given only entries with that protocol are considered
+    fn lookup_name(&self, name: &str, proto: Option<&str>) -> Option<ServiceEntry>;
+
+    /// Find the service on `port`
+    /// If `proto` is given only entries with that protocol are considered
+    fn lookup_port(&self, port: u16, proto: Option<&str>) -> Option<ServiceEntry>;
+}
+
+impl ServiceResolver for ServiceDatabase {
+    fn lookup_name(&self, name: &str, proto: Option<&str>) -> Option<ServiceEntry> {
+        self.get_by_name(name, proto)
+            .or_else(|| self.get_by_alias(name, proto))
+            .cloned()
+    }
+
+    fn lookup_port(&self, port: u16, proto: Option<&str>) -> Option<ServiceEntry> {
+        self.get_by_port(port, proto).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_reader, ParseOptions};
+
+    // Knows a single service, regardless of protocol
+    struct MockResolver;
+
+    impl ServiceResolver for MockResolver {
+        fn lookup_name(&self, name: &str, _proto: Option<&str>) -> Option<ServiceEntry> {
+            if name == "mock" {
+                self.lookup_port(4242, None)
+            } else {
+                None
+            }
+        }
+
+        fn lookup_port(&self, port: u16, _proto: Option<&str>) -> Option<ServiceEntry> {
+            if port == 4242 {
+                ServiceEntry::builder("mock", 4242, "tcp").build().ok()
+            } else {
+                None
+            }
+        }
+    }
+
+    fn port_for(resolver: &dyn ServiceResolver, name: &str) -> Option<u16> {
+        resolver
+            .lookup_name(name, Some("tcp"))
+            .map(|entry| entry.port)
+    }
+
+    #[test]
+    fn resolve_through_trait() {
+        let contents = "http 80/tcp www\nhttp 80/udp www\n";
+        let db = ServiceDatabase::new(
+            parse_reader(contents.as_bytes(), &ParseOptions::default()).unwrap(),
+        );
+
+        assert_eq!(port_for(&db, "www"), Some(80));
+        assert_eq!(port_for(&db, "mock"), None);
+        assert_eq!(
+            db.lookup_port(80, Some("udp")).as_ref(),
+            Some(&db.entries()[1])
+        );
+
+        assert_eq!(port_for(&MockResolver, "mock"), Some(4242));
+        assert_eq!(port_for(&MockResolver, "www"), None);
+    }
+}
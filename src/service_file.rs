@@ -0,0 +1,188 @@
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::{
+    is_comment, open_file, parse_entry, strip_line_ending, ParseError, ParseOptions, ServiceEntry,
+    ServiceFileWriter,
+};
+
+/// A line of a `ServiceFile`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    /// A comment line as written, including the `#` and any leading whitespace
+    Comment(String),
+    /// An empty line, or one with only whitespace
+    Blank,
+    Entry(ServiceEntry),
+    /// A line which isn't a valid entry, kept as written when parsing with `ignore_errs`
+    Unparsed(String),
+}
+
+/// A services file with its comments and blank lines, e.g. to edit entries in code and write the
+/// file back without losing its layout
+/// Lines are read one at a time, so includes aren't followed and each line holds at most one
+/// entry. Entries always keep their comment and raw line.
+#[derive(Debug, Clone)]
+pub struct ServiceFile {
+    pub lines: Vec<Line>,
+    opts: ParseOptions,
+}
+
+impl ServiceFile {
+    /// Parse the lines of `s`, see `parse_str`
+    pub fn parse_str(s: &str, opts: &ParseOptions) -> Result<Self, ParseError> {
+        let opts = ParseOptions {
+            keep_raw: true,
+            keep_comments: true,
+            ..opts.clone()
+        };
+
+        let mut lines = Vec::new();
+        for line in s.split_inclusive('\n') {
+            let line = strip_line_ending(line);
+            let entryline = line.trim_start();
+            if entryline.is_empty() {
+                lines.push(Line::Blank);
+            } else if is_comment(entryline, &opts) {
+                lines.push(Line::Comment(line.to_string()));
+            } else {
+                match parse_entry(entryline, &opts) {
+                    Ok(entry) => lines.push(Line::Entry(ServiceEntry {
+                        raw: Some(line.to_string()),
+                        ..entry
+                    })),
+                    Err(_) if opts.ignore_errs => lines.push(Line::Unparsed(line.to_string())),
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+
+        Ok(ServiceFile { lines, opts })
+    }
+
+    /// Parse the file at `path`, see `parse_file_with_options`
+    pub fn parse_file(path: &Path, opts: &ParseOptions) -> Result<Self, ParseError> {
+        let mut contents = String::new();
+        open_file(path, opts)?.read_to_string(&mut contents)?;
+        Self::parse_str(&contents, opts)
+    }
+
+    /// The entries of the file, in order
+    pub fn entries(&self) -> impl Iterator<Item = &ServiceEntry> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Entry(entry) => Some(entry),
+            _ => None,
+        })
+    }
+
+    /// The entries of the file for editing in place, in order
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = &mut ServiceEntry> {
+        self.lines.iter_mut().filter_map(|line| match line {
+            Line::Entry(entry) => Some(entry),
+            _ => None,
+        })
+    }
+
+    /// Write the file back, each line as it was read unless it's an entry which has changed
+    /// A changed or added entry is formatted like `ServiceFileWriter::write_entry`.
+    pub fn write_to<W: Write>(&self, w: W) -> io::Result<()> {
+        let mut writer = ServiceFileWriter::new(w);
+        for line in &self.lines {
+            match line {
+                Line::Comment(text) | Line::Unparsed(text) => {
+                    writeln!(writer.get_mut(), "{}", text)?
+                }
+                Line::Blank => writeln!(writer.get_mut())?,
+                Line::Entry(entry) => match self.unchanged_raw(entry) {
+                    Some(raw) => writeln!(writer.get_mut(), "{}", raw)?,
+                    None => writer.write_entry(entry)?,
+                },
+            }
+        }
+        writer.flush()
+    }
+
+    // The raw line of `entry` if it still describes the entry
+    fn unchanged_raw<'a>(&self, entry: &'a ServiceEntry) -> Option<&'a str> {
+        let raw = entry.raw.as_deref()?;
+        let parsed = parse_entry(raw.trim_start(), &self.opts).ok()?;
+        let unchanged = parsed
+            == ServiceEntry {
+                raw: None,
+                span: None,
+                ..entry.clone()
+            };
+        Some(raw).filter(|_| unchanged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENTS: &str = "\
+        # /etc/services\n\
+        \n\
+        tcpmux\t1/tcp    # TCP Port Service Multiplexer\n\
+        \x20 # web\n\
+        http   80/tcp   www www-http   # WorldWideWeb HTTP\n\
+        \n\
+        ssh 22/tcp\n\
+        ";
+
+    fn write(file: &ServiceFile) -> String {
+        let mut output = Vec::new();
+        file.write_to(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = ServiceFile::parse_str(CONTENTS, &ParseOptions::default()).unwrap();
+        assert_eq!(file.lines.len(), 7);
+        assert_eq!(file.lines[0], Line::Comment("# /etc/services".to_string()));
+        assert_eq!(file.lines[1], Line::Blank);
+        assert_eq!(file.entries().count(), 3);
+        assert_eq!(write(&file), CONTENTS);
+    }
+
+    #[test]
+    fn edit_entry() {
+        let mut file = ServiceFile::parse_str(CONTENTS, &ParseOptions::default()).unwrap();
+        let http = file
+            .entries_mut()
+            .find(|entry| entry.name == "http")
+            .unwrap();
+        http.port = 8080;
+
+        assert_eq!(
+            write(&file),
+            "\
+            # /etc/services\n\
+            \n\
+            tcpmux\t1/tcp    # TCP Port Service Multiplexer\n\
+            \x20 # web\n\
+            http            8080/tcp        www www-http # WorldWideWeb HTTP\n\
+            \n\
+            ssh 22/tcp\n\
+            "
+        );
+    }
+
+    #[test]
+    fn unparsed_lines() {
+        let contents = "http 80/tcp\nbad line\n";
+        assert_eq!(
+            ServiceFile::parse_str(contents, &ParseOptions::default()).map(|file| file.lines),
+            Err(ParseError::MalformedPort("line".to_string()))
+        );
+
+        let opts = ParseOptions {
+            ignore_errs: true,
+            ..Default::default()
+        };
+        let file = ServiceFile::parse_str(contents, &opts).unwrap();
+        assert_eq!(file.lines[1], Line::Unparsed("bad line".to_string()));
+        assert_eq!(write(&file), contents);
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use crate::ServiceDatabase;
+
+/// The names and aliases of a `ServiceDatabase` arranged for prefix queries, e.g. to autocomplete
+/// service names as they're typed, see `ServiceDatabase::build_name_trie`
+/// Like the database's lookups, prefixes are matched ignoring case.
+#[derive(Debug, Clone)]
+pub struct NameTrie<'a> {
+    // the root is nodes[0]
+    nodes: Vec<Node<'a>>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node<'a> {
+    // next lowercased character -> index into nodes
+    children: BTreeMap<char, usize>,
+    // names ending at this node, as written in the database
+    names: Vec<&'a str>,
+}
+
+impl<'a> NameTrie<'a> {
+    fn new() -> Self {
+        NameTrie {
+            nodes: vec![Node::default()],
+        }
+    }
+
+    fn insert(&mut self, name: &'a str) {
+        let mut node = 0;
+        for c in name.chars().flat_map(char::to_lowercase) {
+            node = match self.nodes[node].children.get(&c) {
+                Some(&child) => child,
+                None => {
+                    self.nodes.push(Node::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.insert(c, child);
+                    child
+                }
+            };
+        }
+
+        let names = &mut self.nodes[node].names;
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    /// Every name and alias starting with `prefix`, in lexicographic order of their lowercased
+    /// forms. A name defined by several entries, e.g. for tcp and udp, is only listed once.
+    pub fn complete(&self, prefix: &str) -> Vec<&'a str> {
+        let mut node = 0;
+        for c in prefix.chars().flat_map(char::to_lowercase) {
+            match self.nodes[node].children.get(&c) {
+                Some(&child) => node = child,
+                None => return Vec::new(),
+            }
+        }
+
+        let mut names = Vec::new();
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            names.extend(&node.names);
+            // reversed so that children are visited in order
+            stack.extend(node.children.values().rev());
+        }
+        names
+    }
+}
+
+impl ServiceDatabase {
+    /// Build a trie of the canonical names and aliases of all entries, for completing prefixes in
+    /// time proportional to the length of the prefix rather than the size of the database
+    pub fn build_name_trie(&self) -> NameTrie<'_> {
+        let mut trie = NameTrie::new();
+        for entry in self.entries() {
+            trie.insert(&entry.name);
+            for alias in &entry.aliases {
+                trie.insert(alias);
+            }
+        }
+        trie
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_reader, ParseOptions};
+
+    fn database(contents: &str) -> ServiceDatabase {
+        ServiceDatabase::new(parse_reader(contents.as_bytes(), &ParseOptions::default()).unwrap())
+    }
+
+    #[test]
+    fn complete() {
+        let db = database(
+            "\
+            tcpmux 1/tcp\n\
+            compressnet 2/udp\n\
+            compressnet 2/tcp\n\
+            compressnet 3/tcp\n\
+            http 80/tcp www www-http\n\
+            WWW-ldap-gw 80/udp\n\
+            ",
+        );
+        let trie = db.build_name_trie();
+
+        assert_eq!(trie.complete("comp"), vec!("compressnet"));
+        assert_eq!(trie.complete("COMP"), vec!("compressnet"));
+        assert_eq!(trie.complete("www"), vec!("www", "www-http", "WWW-ldap-gw"));
+        assert_eq!(trie.complete("compx"), Vec::<&str>::new());
+        assert_eq!(trie.complete("").len(), 6);
+    }
+}
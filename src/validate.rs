@@ -0,0 +1,318 @@
+#[cfg(not(feature = "std"))]
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec::Vec,
+};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::ServiceEntry;
+
+/// A problem with a single entry, see `ServiceEntry::validate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryProblem {
+    EmptyName,
+    /// Service names should start with a letter, see RFC 6335 section 5.1
+    NameStartsWithDigit,
+    /// Port 0 is reserved
+    ZeroPort,
+    EmptyProtocol,
+    /// An alias which is the same as the service name, ignoring case, holds the alias
+    AliasIsName(String),
+}
+
+impl ServiceEntry {
+    /// Check the entry for problems, e.g. after constructing it in code
+    /// Returns every problem found rather than stopping at the first, in the order of the fields.
+    pub fn validate(&self) -> Vec<EntryProblem> {
+        let mut problems = Vec::new();
+
+        match self.name.chars().next() {
+            None => problems.push(EntryProblem::EmptyName),
+            Some(c) if c.is_ascii_digit() => problems.push(EntryProblem::NameStartsWithDigit),
+            Some(_) => {}
+        }
+        if self.port == 0 {
+            problems.push(EntryProblem::ZeroPort);
+        }
+        if self.protocol.is_empty() {
+            problems.push(EntryProblem::EmptyProtocol);
+        }
+
+        let name = self.name.to_lowercase();
+        problems.extend(
+            self.aliases
+                .iter()
+                .filter(|alias| alias.to_lowercase() == name)
+                .map(|alias| EntryProblem::AliasIsName(alias.clone())),
+        );
+
+        problems
+    }
+
+    /// The aliases which aren't valid service names, in order: empty, starting with a digit, or
+    /// containing a `/`, `#` or whitespace, e.g. `80/tcp` after a column shift put the port in
+    /// the aliases
+    pub fn validate_aliases(&self) -> Vec<String> {
+        self.aliases
+            .iter()
+            .filter(|alias| {
+                alias.chars().next().is_none_or(|c| c.is_ascii_digit())
+                    || alias.contains(|c: char| c == '/' || c == '#' || c.is_whitespace())
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// A problem between entries, or between the aliases of an entry, see `validate`
+/// Names and aliases are compared ignoring case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// The port and protocol are used by services with different names, holds the name of the
+    /// first entry to use them and the name of a later one
+    PortReused {
+        port: u16,
+        protocol: String,
+        name: String,
+        other_name: String,
+    },
+    /// An alias of the service `name` is the name of another service, on `other_port`
+    AliasIsOtherName {
+        name: String,
+        alias: String,
+        other_port: u16,
+    },
+    /// The entry for the service `name` on `port` lists `alias` more than once
+    DuplicateAlias {
+        name: String,
+        port: u16,
+        alias: String,
+    },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conflict::PortReused {
+                port,
+                protocol,
+                name,
+                other_name,
+            } => write!(
+                f,
+                "{}/{} is used by both \"{}\" and \"{}\"",
+                port, protocol, name, other_name
+            ),
+            Conflict::AliasIsOtherName {
+                name,
+                alias,
+                other_port,
+            } => write!(
+                f,
+                "alias \"{}\" of \"{}\" is also the service on port {}",
+                alias, name, other_port
+            ),
+            Conflict::DuplicateAlias { name, port, alias } => write!(
+                f,
+                "alias \"{}\" is listed more than once for \"{}\" on port {}",
+                alias, name, port
+            ),
+        }
+    }
+}
+
+/// Check parsed entries for conflicts between them, e.g. to reject a change to a services file in
+/// CI. Unlike parse errors these are problems with what the entries mean together, see also
+/// `ServiceEntry::validate` for problems with a single entry.
+/// Conflicts are listed in the order of the entries they were found at, each reported once. The
+/// port of a ranged entry is the start of its range.
+pub fn validate(entries: &[ServiceEntry]) -> Vec<Conflict> {
+    // lowercased name -> port of the first entry with that name
+    let mut name_ports = BTreeMap::new();
+    for entry in entries {
+        name_ports
+            .entry(entry.name.to_lowercase())
+            .or_insert(entry.port);
+    }
+
+    let mut conflicts = Vec::new();
+    // (port, lowercased protocol) -> the first entry using them
+    let mut port_entries: BTreeMap<_, &ServiceEntry> = BTreeMap::new();
+    // the conflicts reported so far, by lowercased names, so that each is only reported once
+    let mut reused_ports = BTreeSet::new();
+    let mut duplicate_aliases = BTreeSet::new();
+    let mut other_names = BTreeSet::new();
+    for entry in entries {
+        let name = entry.name.to_lowercase();
+        let key = (entry.port, entry.protocol.to_lowercase());
+        match port_entries.get(&key) {
+            Some(first) if first.name.to_lowercase() != name => {
+                if reused_ports.insert((key.clone(), name.clone())) {
+                    conflicts.push(Conflict::PortReused {
+                        port: entry.port,
+                        protocol: entry.protocol.clone(),
+                        name: first.name.clone(),
+                        other_name: entry.name.clone(),
+                    });
+                }
+            }
+            Some(_) => {}
+            None => {
+                port_entries.insert(key, entry);
+            }
+        }
+
+        let mut aliases = BTreeSet::new();
+        for alias in &entry.aliases {
+            let folded = alias.to_lowercase();
+            if !aliases.insert(folded.clone()) {
+                if duplicate_aliases.insert((name.clone(), entry.port, folded)) {
+                    conflicts.push(Conflict::DuplicateAlias {
+                        name: entry.name.clone(),
+                        port: entry.port,
+                        alias: alias.clone(),
+                    });
+                }
+                continue;
+            }
+            // an alias which is the entry's own name is an `EntryProblem`
+            if folded == name {
+                continue;
+            }
+            if let Some(&other_port) = name_ports.get(&folded) {
+                if other_names.insert((name.clone(), folded)) {
+                    conflicts.push(Conflict::AliasIsOtherName {
+                        name: entry.name.clone(),
+                        alias: alias.clone(),
+                        other_port,
+                    });
+                }
+            }
+        }
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_entry() {
+        let entry: ServiceEntry = "http 80/tcp www www-http".parse().unwrap();
+        assert_eq!(entry.validate(), vec!());
+    }
+
+    fn entries(lines: &[&str]) -> Vec<ServiceEntry> {
+        lines.iter().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn no_conflicts() {
+        let entries = entries(&[
+            "http 80/tcp www www-http",
+            "http 80/udp www www-http",
+            "HTTP 80/tcp",
+            "9p 564/tcp 9P",
+            "gopher 70/tcp",
+        ]);
+        assert_eq!(validate(&entries), vec!());
+    }
+
+    #[test]
+    fn port_reused() {
+        let entries = entries(&["Http 80/tcp", "www 80/udp", "web 80/tcp", "WEB 80/tcp"]);
+        let conflicts = validate(&entries);
+        assert_eq!(
+            conflicts,
+            vec!(Conflict::PortReused {
+                port: 80,
+                protocol: "tcp".to_string(),
+                name: "Http".to_string(),
+                other_name: "web".to_string(),
+            })
+        );
+        assert_eq!(
+            conflicts[0].to_string(),
+            "80/tcp is used by both \"Http\" and \"web\""
+        );
+    }
+
+    #[test]
+    fn alias_is_other_name() {
+        let entries = entries(&["http 80/tcp www", "http 80/udp www", "WWW 8080/tcp"]);
+        assert_eq!(
+            validate(&entries),
+            vec!(Conflict::AliasIsOtherName {
+                name: "http".to_string(),
+                alias: "www".to_string(),
+                other_port: 8080,
+            })
+        );
+    }
+
+    #[test]
+    fn duplicate_alias() {
+        let entries = entries(&["http 80/tcp www web WWW www", "http 80/udp www"]);
+        assert_eq!(
+            validate(&entries),
+            vec!(Conflict::DuplicateAlias {
+                name: "http".to_string(),
+                port: 80,
+                alias: "WWW".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_aliases() {
+        let entry: ServiceEntry = "http 80/tcp www 80/tcp www-http".parse().unwrap();
+        assert_eq!(entry.validate_aliases(), vec!("80/tcp"));
+
+        let entry = ServiceEntry {
+            aliases: vec![
+                "".to_string(),
+                "9p".to_string(),
+                "plan9".to_string(),
+                "two words".to_string(),
+                "a#b".to_string(),
+            ],
+            ..entry
+        };
+        assert_eq!(entry.validate_aliases(), vec!("", "9p", "two words", "a#b"));
+        let entry: ServiceEntry = "http 80/tcp www www-http".parse().unwrap();
+        assert!(entry.validate_aliases().is_empty());
+    }
+
+    #[test]
+    fn broken_entry() {
+        let entry = ServiceEntry {
+            name: "9p".to_string(),
+            port: 0,
+            aliases: vec!["plan9".to_string(), "9P".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            entry.validate(),
+            vec!(
+                EntryProblem::NameStartsWithDigit,
+                EntryProblem::ZeroPort,
+                EntryProblem::EmptyProtocol,
+                EntryProblem::AliasIsName("9P".to_string()),
+            )
+        );
+
+        assert_eq!(
+            ServiceEntry::default().validate(),
+            vec!(
+                EntryProblem::EmptyName,
+                EntryProblem::ZeroPort,
+                EntryProblem::EmptyProtocol
+            )
+        );
+    }
+}
@@ -0,0 +1,83 @@
+#[cfg(not(feature = "std"))]
+use alloc::string::ToString;
+
+use crate::ServiceEntry;
+
+/// A service known at compile time, see `ServiceEntry::HTTP` and friends
+/// Use `ServiceEntry::from` to get a full entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WellKnownService {
+    pub name: &'static str,
+    pub port: u16,
+    pub protocol: &'static str,
+}
+
+impl ServiceEntry {
+    pub const FTP: WellKnownService = WellKnownService {
+        name: "ftp",
+        port: 21,
+        protocol: "tcp",
+    };
+    pub const SSH: WellKnownService = WellKnownService {
+        name: "ssh",
+        port: 22,
+        protocol: "tcp",
+    };
+    pub const SMTP: WellKnownService = WellKnownService {
+        name: "smtp",
+        port: 25,
+        protocol: "tcp",
+    };
+    pub const DOMAIN: WellKnownService = WellKnownService {
+        name: "domain",
+        port: 53,
+        protocol: "udp",
+    };
+    pub const HTTP: WellKnownService = WellKnownService {
+        name: "http",
+        port: 80,
+        protocol: "tcp",
+    };
+    pub const HTTPS: WellKnownService = WellKnownService {
+        name: "https",
+        port: 443,
+        protocol: "tcp",
+    };
+}
+
+impl From<WellKnownService> for ServiceEntry {
+    fn from(service: WellKnownService) -> Self {
+        ServiceEntry {
+            name: service.name.to_string(),
+            port: service.port,
+            protocol: service.protocol.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iana_ports() {
+        let services = [
+            (ServiceEntry::FTP, 21),
+            (ServiceEntry::SSH, 22),
+            (ServiceEntry::SMTP, 25),
+            (ServiceEntry::DOMAIN, 53),
+            (ServiceEntry::HTTP, 80),
+            (ServiceEntry::HTTPS, 443),
+        ];
+        for (service, port) in &services {
+            assert_eq!(service.port, *port);
+        }
+    }
+
+    #[test]
+    fn into_entry() {
+        let entry = ServiceEntry::from(ServiceEntry::HTTP);
+        assert_eq!(entry, "http 80/tcp".parse::<ServiceEntry>().unwrap());
+    }
+}
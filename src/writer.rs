@@ -0,0 +1,331 @@
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+use crate::ServiceEntry;
+
+/// How the columns of an entry are separated, see `WriteOptions`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Separator {
+    /// A single tab between columns, without any padding
+    Tab,
+    /// Pad with spaces so that columns line up, the port/protocol field to this many characters
+    Spaces(usize),
+}
+
+/// Controls the layout of the lines written by `ServiceFileWriter`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WriteOptions {
+    pub separator: Separator,
+    /// The width the name is padded to with `Separator::Spaces`
+    pub name_width: usize,
+}
+
+/// The column widths of a typical /etc/services
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            separator: Separator::Spaces(15),
+            name_width: 15,
+        }
+    }
+}
+
+// The first field of `entry` which wouldn't be read back as written, as `(field, value)`
+pub(crate) fn unwritable_field(entry: &ServiceEntry) -> Option<(&'static str, &str)> {
+    if entry.name.contains(char::is_whitespace) {
+        return Some(("name", &entry.name));
+    }
+    if let Some(alias) = entry
+        .aliases
+        .iter()
+        .find(|alias| alias.contains(char::is_whitespace))
+    {
+        return Some(("alias", alias));
+    }
+    match &entry.comment {
+        Some(comment) if comment.contains(['\n', '\r']) => Some(("comment", comment)),
+        _ => None,
+    }
+}
+
+// `entry` with the fields `unwritable_field` rejects escaped: whitespace in a name or alias is
+// replaced by `_` like `ServiceEntry::escaped_name`, and a line break in the comment by a space
+pub(crate) fn escape_unwritable(entry: &ServiceEntry) -> Cow<'_, ServiceEntry> {
+    if unwritable_field(entry).is_none() {
+        return Cow::Borrowed(entry);
+    }
+
+    let escape = |name: &String| name.replace(char::is_whitespace, "_");
+    Cow::Owned(ServiceEntry {
+        name: escape(&entry.name),
+        aliases: entry.aliases.iter().map(escape).collect(),
+        comment: entry
+            .comment
+            .as_ref()
+            .map(|comment| comment.replace(['\n', '\r'], " ")),
+        ..entry.clone()
+    })
+}
+
+/// Writes entries in the services(5) format as they are produced, without collecting them first
+/// By default columns are padded so that the port/protocol and alias fields line up.
+pub struct ServiceFileWriter<W: Write> {
+    inner: W,
+    options: WriteOptions,
+}
+
+impl<W: Write> ServiceFileWriter<W> {
+    /// Create a writer using the column widths of a typical /etc/services
+    pub fn new(inner: W) -> Self {
+        Self::with_options(inner, WriteOptions::default())
+    }
+
+    /// Create a writer which pads the name to `name_width` and the port/protocol field to
+    /// `port_width` characters. Fields which are too long are separated by a single space.
+    pub fn with_columns(inner: W, name_width: usize, port_width: usize) -> Self {
+        Self::with_options(
+            inner,
+            WriteOptions {
+                separator: Separator::Spaces(port_width),
+                name_width,
+            },
+        )
+    }
+
+    /// Create a writer laying out lines according to `options`
+    pub fn with_options(inner: W, options: WriteOptions) -> Self {
+        ServiceFileWriter { inner, options }
+    }
+
+    /// Write `entry` as a single line
+    /// Fails with `io::ErrorKind::InvalidInput` if the name or an alias contains whitespace, which
+    /// would be read back as separate fields, see `ServiceEntry::escaped_name`, or if the comment
+    /// contains a line break.
+    pub fn write_entry(&mut self, entry: &ServiceEntry) -> io::Result<()> {
+        if let Some((field, value)) = unwritable_field(entry) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} {:?} can't be written on a single line", field, value),
+            ));
+        }
+
+        let line = match self.options.separator {
+            Separator::Tab => format!(
+                "{}\t{}\t{}",
+                entry.name,
+                entry.port_protocol(),
+                entry.aliases.join(" ")
+            ),
+            Separator::Spaces(port_width) => format!(
+                "{:<name_width$} {:<port_width$} {}",
+                entry.name,
+                entry.port_protocol(),
+                entry.aliases.join(" "),
+                name_width = self.options.name_width,
+                port_width = port_width,
+            ),
+        };
+        // a disabled entry is written commented out
+        if entry.disabled {
+            write!(self.inner, "#")?;
+        }
+        match &entry.comment {
+            Some(comment) => writeln!(self.inner, "{} # {}", line.trim_end(), comment),
+            None => writeln!(self.inner, "{}", line.trim_end()),
+        }
+    }
+
+    /// Write `text` as a comment, each line of `text` becomes a separate comment line
+    pub fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        for line in text.lines() {
+            if line.is_empty() {
+                writeln!(self.inner, "#")?;
+            } else {
+                writeln!(self.inner, "# {}", line)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// The underlying writer, e.g. to write lines which aren't entries or comments as they are
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Get back the underlying writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_reader, ParseOptions};
+
+    #[test]
+    fn write_and_reparse() {
+        let entries: Vec<ServiceEntry> = vec![
+            "tcpmux 1/tcp".parse().unwrap(),
+            "http 80/tcp www www-http".parse().unwrap(),
+            "a-very-long-service-name 12345/udp".parse().unwrap(),
+        ];
+
+        let mut writer = ServiceFileWriter::new(Vec::new());
+        writer.write_comment("Network services\n\nlocal").unwrap();
+        for entry in &entries {
+            writer.write_entry(entry).unwrap();
+        }
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+
+        assert_eq!(
+            output,
+            "\
+            # Network services\n\
+            #\n\
+            # local\n\
+            tcpmux          1/tcp\n\
+            http            80/tcp          www www-http\n\
+            a-very-long-service-name 12345/udp\n\
+            "
+        );
+        assert_eq!(
+            parse_reader(output.as_bytes(), &ParseOptions::default()),
+            Ok(entries)
+        );
+    }
+
+    #[test]
+    fn write_name_with_whitespace() {
+        let mut writer = ServiceFileWriter::new(Vec::new());
+        let entry = ServiceEntry {
+            name: "my service".to_string(),
+            port: 8080,
+            protocol: "tcp".to_string(),
+            ..Default::default()
+        };
+        let err = writer.write_entry(&entry).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let entry = ServiceEntry {
+            name: "myservice".to_string(),
+            aliases: vec!["alt name".to_string()],
+            ..entry
+        };
+        assert!(writer.write_entry(&entry).is_err());
+        assert!(writer.into_inner().is_empty());
+    }
+
+    #[test]
+    fn write_multiline_comment() {
+        let mut writer = ServiceFileWriter::new(Vec::new());
+        for comment in &["World Wide Web\nnbp 2/ddp", "World Wide Web\r"] {
+            let entry = ServiceEntry {
+                name: "http".to_string(),
+                port: 80,
+                protocol: "tcp".to_string(),
+                comment: Some(comment.to_string()),
+                ..Default::default()
+            };
+            let err = writer.write_entry(&entry).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        assert!(writer.into_inner().is_empty());
+    }
+
+    #[test]
+    fn write_separators() {
+        let entries: Vec<ServiceEntry> = vec![
+            "tcpmux 1/tcp".parse().unwrap(),
+            "http 80/tcp www www-http".parse().unwrap(),
+        ];
+        let write = |options: WriteOptions| {
+            let mut writer = ServiceFileWriter::with_options(Vec::new(), options);
+            for entry in &entries {
+                writer.write_entry(entry).unwrap();
+            }
+            String::from_utf8(writer.into_inner()).unwrap()
+        };
+
+        let output = write(WriteOptions {
+            separator: Separator::Tab,
+            ..Default::default()
+        });
+        assert_eq!(output, "tcpmux\t1/tcp\nhttp\t80/tcp\twww www-http\n");
+        assert_eq!(
+            parse_reader(output.as_bytes(), &ParseOptions::default()),
+            Ok(entries.clone())
+        );
+
+        let output = write(WriteOptions {
+            separator: Separator::Spaces(8),
+            name_width: 8,
+        });
+        assert_eq!(output, "tcpmux   1/tcp\nhttp     80/tcp   www www-http\n");
+        assert_eq!(
+            parse_reader(output.as_bytes(), &ParseOptions::default()),
+            Ok(entries.clone())
+        );
+    }
+
+    #[test]
+    fn write_unaligned_range() {
+        let opts = ParseOptions {
+            allow_port_ranges: true,
+            ..Default::default()
+        };
+        let entries = parse_reader("myservice 8000-8010/tcp\n".as_bytes(), &opts).unwrap();
+
+        let mut writer = ServiceFileWriter::with_columns(Vec::new(), 0, 0);
+        writer.write_entry(&entries[0]).unwrap();
+        let output = writer.into_inner();
+
+        assert_eq!(output, b"myservice 8000-8010/tcp\n");
+        assert_eq!(parse_reader(&output[..], &opts), Ok(entries));
+    }
+
+    #[test]
+    fn write_comment_of_entry() {
+        let opts = ParseOptions {
+            keep_comments: true,
+            ..Default::default()
+        };
+        let entries = parse_reader(
+            "http 80/tcp www # World Wide Web\nnbp 2/ddp\n".as_bytes(),
+            &opts,
+        )
+        .unwrap();
+
+        let mut writer = ServiceFileWriter::with_columns(Vec::new(), 0, 0);
+        for entry in &entries {
+            writer.write_entry(entry).unwrap();
+        }
+        let output = writer.into_inner();
+
+        assert_eq!(output, b"http 80/tcp www # World Wide Web\nnbp 2/ddp\n");
+        assert_eq!(parse_reader(&output[..], &opts), Ok(entries));
+    }
+
+    #[test]
+    fn write_disabled_entry() {
+        let opts = ParseOptions {
+            parse_disabled: true,
+            ..Default::default()
+        };
+        let entries = parse_reader("#http 80/tcp www\nnbp 2/ddp\n".as_bytes(), &opts).unwrap();
+
+        let mut writer = ServiceFileWriter::with_columns(Vec::new(), 0, 0);
+        for entry in &entries {
+            writer.write_entry(entry).unwrap();
+        }
+        let output = writer.into_inner();
+
+        assert_eq!(output, b"#http 80/tcp www\nnbp 2/ddp\n");
+        assert_eq!(parse_reader(&output[..], &opts), Ok(entries));
+    }
+}